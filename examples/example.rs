@@ -2,7 +2,7 @@ use std::borrow::Cow::{self, Borrowed, Owned};
 
 use rustyline::completion::FilenameCompleter;
 use rustyline::error::ReadlineError;
-use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
+use rustyline::highlight::{Highlighter, LsColors, MatchingBracketHighlighter};
 use rustyline::hint::HistoryHinter;
 use rustyline::validate::MatchingBracketValidator;
 use rustyline::{Cmd, CompletionType, Config, EditMode, Editor, KeyEvent};
@@ -44,6 +44,14 @@ impl Highlighter for MyHelper {
     fn highlight_char(&self, line: &str, pos: usize) -> bool {
         self.highlighter.highlight_char(line, pos)
     }
+
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        completion: CompletionType,
+    ) -> Cow<'c, str> {
+        self.completer.highlight_candidate(candidate, completion)
+    }
 }
 
 // To debug rustyline:
@@ -56,7 +64,9 @@ fn main() -> rustyline::Result<()> {
         .edit_mode(EditMode::Emacs)
         .build();
     let h = MyHelper {
-        completer: FilenameCompleter::new(),
+        completer: FilenameCompleter::builder()
+            .ls_colors(LsColors::from_env())
+            .build(),
         highlighter: MatchingBracketHighlighter::new(),
         hinter: HistoryHinter {},
         colored_prompt: "".to_owned(),
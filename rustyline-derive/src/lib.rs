@@ -59,9 +59,37 @@ pub fn completer_macro_derive(input: TokenStream) -> TokenStream {
                     ::rustyline::completion::Completer::complete(&self.#field_name_or_index, line, pos, ctx)
                 }
 
+                fn completion(
+                    &self,
+                    line: &str,
+                    pos: usize,
+                    ctx: &::rustyline::Context<'_>,
+                ) -> ::rustyline::Result<::rustyline::completion::Completion<Self::Candidate>> {
+                    ::rustyline::completion::Completer::completion(&self.#field_name_or_index, line, pos, ctx)
+                }
+
+                fn complete_in_background(
+                    &self,
+                    line: &str,
+                    pos: usize,
+                    ctx: &::rustyline::Context<'_>,
+                ) -> ::std::option::Option<::rustyline::completion::PendingCompletion<Self::Candidate>> {
+                    ::rustyline::completion::Completer::complete_in_background(&self.#field_name_or_index, line, pos, ctx)
+                }
+
                 fn update(&self, line: &mut ::rustyline::line_buffer::LineBuffer, start: usize, elected: &str) {
                     ::rustyline::completion::Completer::update(&self.#field_name_or_index, line, start, elected)
                 }
+
+                fn update_candidate(
+                    &self,
+                    line: &mut ::rustyline::line_buffer::LineBuffer,
+                    start: usize,
+                    candidate: &Self::Candidate,
+                    elected: &str,
+                ) {
+                    ::rustyline::completion::Completer::update_candidate(&self.#field_name_or_index, line, start, candidate, elected)
+                }
             }
         }
     } else {
@@ -0,0 +1,353 @@
+//! [clap](https://docs.rs/clap) integration: completion, hints and
+//! validation derived from a `clap::Command` (feature `with-clap`).
+//!
+//! Each line is parsed as the arguments of the `clap::Command` (without
+//! binary name): its subcommands are the commands of the REPL.
+use clap::error::ErrorKind;
+use clap::{Arg, ValueHint};
+
+use crate::completion::{
+    split_words, unquote, walk_words, ArgKind, CommandCompleter, CommandSpec, CommandTree,
+    Completer, Completion, DescribedPair, Flag, WordWalk,
+};
+use crate::highlight::Highlighter;
+use crate::hint::{Hint, Hinter};
+use crate::line_buffer::LineBuffer;
+use crate::validate::{ValidationContext, ValidationResult, Validator};
+use crate::{Context, Helper, Result};
+
+/// A `Helper` which completes, hints and validates the lines according to a
+/// `clap::Command`:
+/// - subcommands, long and short flags, possible values and paths (depending
+///   on `ValueHint`) are completed,
+/// - the expected next argument is hinted (like `<FILE>`),
+/// - clap parse errors are reported before the line is accepted.
+pub struct ClapHelper {
+    command: clap::Command,
+    completer: CommandCompleter,
+}
+
+impl ClapHelper {
+    /// Constructor
+    #[must_use]
+    pub fn new(command: clap::Command) -> Self {
+        let mut command = command.no_binary_name(true);
+        command.build();
+        let completer = CommandCompleter::from_root(to_command_spec(&command));
+        Self { command, completer }
+    }
+
+    /// The expected next argument, where the walk stopped
+    fn next_arg(walk: &WordWalk<'_, clap::Command>) -> Option<String> {
+        let (command, positional) = (walk.command, walk.positional);
+        if let Some(arg) = walk.flag_value {
+            return Some(format!("<{}>", value_name(arg)));
+        }
+        if positional == 0 && command.has_subcommands() {
+            let name = command.get_subcommand_value_name().unwrap_or("COMMAND");
+            return Some(format!("<{}>", name));
+        }
+        let positionals = positionals(command);
+        let arg = match positionals.get(positional) {
+            Some(arg) => arg,
+            None => positionals.last().filter(|a| is_variadic(a))?,
+        };
+        let name = value_name(arg);
+        let variadic = if is_variadic(arg) { "..." } else { "" };
+        Some(if arg.is_required_set() {
+            format!("<{}>{}", name, variadic)
+        } else {
+            format!("[{}]{}", name, variadic)
+        })
+    }
+}
+
+impl Completer for ClapHelper {
+    type Candidate = DescribedPair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<DescribedPair>)> {
+        self.completer.complete(line, pos, ctx)
+    }
+
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<DescribedPair>> {
+        self.completer.completion(line, pos, ctx)
+    }
+
+    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+        self.completer.update(line, start, elected);
+    }
+
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &DescribedPair,
+        elected: &str,
+    ) {
+        self.completer
+            .update_candidate(line, start, candidate, elected);
+    }
+}
+
+/// Expected next argument, displayed but never inserted.
+pub struct ArgHint(String);
+
+impl Hint for ArgHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Hinter for ClapHelper {
+    type Hint = ArgHint;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<ArgHint> {
+        if pos < line.len() || line.trim().is_empty() {
+            return None;
+        }
+        let walk = walk_words(&self.command, line, pos);
+        if walk.current.is_some() {
+            return None; // the cursor is at the end of a word
+        }
+        Self::next_arg(&walk).map(|hint| ArgHint(format!(" {}", hint)))
+    }
+}
+
+impl Validator for ClapHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        let input = ctx.input();
+        if input.trim().is_empty() {
+            return Ok(ValidationResult::Valid(None));
+        }
+        let args: Vec<String> = split_words(input).into_iter().map(unquote).collect();
+        Ok(match self.command.clone().try_get_matches_from(args) {
+            Ok(_) => ValidationResult::Valid(None),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::DisplayHelp
+                        | ErrorKind::DisplayVersion
+                        | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+                ) =>
+            {
+                ValidationResult::Valid(None)
+            }
+            Err(err) => {
+                let msg = err.to_string();
+                let msg = msg.lines().next().unwrap_or_default();
+                ValidationResult::Invalid(Some(format!("\n{}", msg)))
+            }
+        })
+    }
+}
+
+impl Highlighter for ClapHelper {}
+
+impl CommandTree for clap::Command {
+    type Value = Arg;
+
+    fn subcommand(&self, name: &str) -> Option<&Self> {
+        self.find_subcommand(name)
+    }
+
+    fn flag_value(&self, name: &str) -> Option<&Arg> {
+        if let Some(long) = name.strip_prefix("--") {
+            self.get_arguments().find(|a| a.get_long() == Some(long))
+        } else {
+            let short = name.strip_prefix('-').and_then(|s| s.chars().next());
+            self.get_arguments().find(|a| a.get_short() == short)
+        }
+        .filter(|a| a.get_action().takes_values())
+    }
+}
+
+impl Helper for ClapHelper {}
+
+fn to_command_spec(command: &clap::Command) -> CommandSpec {
+    let mut result = CommandSpec::new(command.get_name());
+    if let Some(about) = command.get_about() {
+        result = result.with_description(&about.to_string());
+    }
+    for subcommand in command.get_subcommands().filter(|c| !c.is_hide_set()) {
+        result = result.with_subcommand(to_command_spec(subcommand));
+    }
+    for arg in command
+        .get_arguments()
+        .filter(|a| !a.is_positional() && !a.is_hide_set())
+    {
+        let mut flag = match (arg.get_long(), arg.get_short()) {
+            (Some(long), Some(short)) => Flag::long(long).with_short(short),
+            (Some(long), None) => Flag::long(long),
+            (None, Some(short)) => Flag::short(short),
+            (None, None) => continue,
+        };
+        if arg.get_action().takes_values() {
+            flag = flag.with_value(arg_kind(arg));
+        }
+        if let Some(help) = arg.get_help() {
+            flag = flag.with_description(&help.to_string());
+        }
+        result = result.with_flag(flag);
+    }
+    for arg in positionals(command) {
+        result = if is_variadic(arg) {
+            result.with_args(arg_kind(arg))
+        } else {
+            result.with_arg(arg_kind(arg))
+        };
+    }
+    result
+}
+
+fn arg_kind(arg: &Arg) -> ArgKind {
+    let values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_owned())
+        .collect();
+    if !values.is_empty() {
+        return ArgKind::Choices(values);
+    }
+    match arg.get_value_hint() {
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::ExecutablePath => ArgKind::File,
+        ValueHint::DirPath => ArgKind::Directory,
+        _ => ArgKind::Any,
+    }
+}
+
+/// Positional arguments, in order
+fn positionals(command: &clap::Command) -> Vec<&Arg> {
+    let mut positionals: Vec<&Arg> = command.get_positionals().collect();
+    positionals.sort_by_key(|a| a.get_index());
+    positionals
+}
+
+fn is_variadic(arg: &Arg) -> bool {
+    matches!(arg.get_num_args(), Some(range) if range.max_values() > 1)
+}
+
+fn value_name(arg: &Arg) -> String {
+    match arg.get_value_names().and_then(|names| names.first()) {
+        Some(name) => name.to_string(),
+        None => arg.get_id().as_str().to_uppercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClapHelper;
+    use crate::completion::Completer;
+    use crate::hint::{Hint, Hinter};
+    use clap::{Arg, ArgAction, ValueHint};
+
+    fn helper() -> ClapHelper {
+        ClapHelper::new(
+            clap::Command::new("repl")
+                .subcommand(
+                    clap::Command::new("open")
+                        .about("Open a file")
+                        .arg(
+                            Arg::new("mode")
+                                .long("mode")
+                                .short('m')
+                                .value_parser(["read", "write"]),
+                        )
+                        .arg(
+                            Arg::new("file")
+                                .required(true)
+                                .value_hint(ValueHint::FilePath),
+                        ),
+                )
+                .subcommand(
+                    clap::Command::new("quit").arg(
+                        Arg::new("force")
+                            .long("force")
+                            .action(ArgAction::SetTrue)
+                            .help("Do not ask"),
+                    ),
+                ),
+        )
+    }
+
+    #[test]
+    fn complete() {
+        let helper = helper();
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+        let complete = |line: &str| {
+            let (start, candidates) = helper.complete(line, line.len(), &ctx).unwrap();
+            let candidates: Vec<_> = candidates.into_iter().map(|c| c.replacement).collect();
+            (start, candidates)
+        };
+        assert_eq!((0, vec!["open".to_owned()]), complete("o"));
+        assert_eq!((5, vec!["--force".to_owned()]), complete("quit --f"));
+        assert_eq!((8, vec!["write".to_owned()]), complete("open -m w"));
+        assert_eq!(
+            Some("Do not ask".to_owned()),
+            helper
+                .complete("quit --f", 8, &ctx)
+                .unwrap()
+                .1
+                .remove(0)
+                .description
+        );
+    }
+
+    #[test]
+    fn hint() {
+        let helper = helper();
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+        let hint = |line: &str| {
+            helper
+                .hint(line, line.len(), &ctx)
+                .map(|h| h.display().to_owned())
+        };
+        assert_eq!(None, hint(""));
+        assert_eq!(None, hint("op"));
+        assert_eq!(Some(" <FILE>".to_owned()), hint("open "));
+        assert_eq!(Some(" <MODE>".to_owned()), hint("open --mode "));
+        assert_eq!(Some(" <FILE>".to_owned()), hint("open -m read "));
+        assert_eq!(None, hint("open -m read f.txt "));
+    }
+
+    #[test]
+    fn validate() {
+        use crate::validate::{ValidationContext, ValidationResult, Validator};
+        let helper = helper();
+        let validate = |mut line: &str| {
+            helper
+                .validate(&mut ValidationContext::new(&mut line))
+                .unwrap()
+        };
+        assert!(matches!(validate(""), ValidationResult::Valid(None)));
+        assert!(matches!(
+            validate("open 'a b.txt'"),
+            ValidationResult::Valid(None)
+        ));
+        assert!(matches!(validate("help"), ValidationResult::Valid(None)));
+        assert!(matches!(
+            validate("open -m exec a.txt"),
+            ValidationResult::Invalid(Some(_))
+        ));
+        assert!(matches!(
+            validate("close"),
+            ValidationResult::Invalid(Some(_))
+        ));
+    }
+}
@@ -22,6 +22,44 @@ pub fn execute<H: Helper>(
     input_state: &InputState,
     kill_ring: &Arc<Mutex<KillRing>>,
     config: &Config,
+) -> Result<Status> {
+    use Status::Proceed;
+
+    let mut replace_selection = false;
+    if s.selection().is_some() {
+        match cmd {
+            Cmd::Select(_) | Cmd::CopySelection | Cmd::CutSelection => {}
+            Cmd::Kill(Movement::BackwardChar(_) | Movement::ForwardChar(_)) => {
+                // Delete / Backspace only remove the selected text
+                s.edit_delete_selection()?;
+                return Ok(Proceed);
+            }
+            Cmd::SelfInsert(..) | Cmd::Insert(..) | Cmd::Yank(..) => {
+                // typed or pasted text replaces the selected text
+                s.changes.borrow_mut().begin();
+                s.edit_delete_selection()?;
+                replace_selection = true;
+            }
+            _ => {
+                s.reset_selection();
+                s.refresh_line()?;
+            }
+        }
+    }
+
+    let status = execute_cmd(cmd, s, input_state, kill_ring, config);
+    if replace_selection {
+        s.changes.borrow_mut().end();
+    }
+    status
+}
+
+fn execute_cmd<H: Helper>(
+    cmd: Cmd,
+    s: &mut State<'_, '_, H>,
+    input_state: &InputState,
+    kill_ring: &Arc<Mutex<KillRing>>,
+    config: &Config,
 ) -> Result<Status> {
     use Status::{Proceed, Submit};
 
@@ -113,6 +151,25 @@ pub fn execute<H: Helper>(
                 s.edit_yank(input_state, text, anchor, n)?;
             }
         }
+        Cmd::Select(ref mvt) if config.enable_selection() => {
+            s.edit_select(mvt)?;
+        }
+        Cmd::CopySelection | Cmd::CutSelection => {
+            if let Some(range) = s.selection() {
+                {
+                    let mut kill_ring = kill_ring.lock().unwrap();
+                    // new entry, not appended to a previous kill
+                    kill_ring.reset();
+                    kill_ring.kill(&s.line[range], Mode::Append);
+                }
+                if cmd == Cmd::CutSelection {
+                    s.edit_delete_selection()?;
+                }
+            }
+        }
+        Cmd::YankLastArg(nth) => s.edit_yank_last_arg(nth)?,
+        Cmd::DabbrevExpand => s.edit_dabbrev_expand()?,
+        Cmd::YankNthArg(nth) => s.edit_yank_nth_arg(nth)?,
         Cmd::ViYankTo(ref mvt) => {
             if let Some(text) = s.line.copy(mvt) {
                 let mut kill_ring = kill_ring.lock().unwrap();
@@ -207,11 +264,25 @@ pub fn execute<H: Helper>(
             }
         }
         Cmd::Move(Movement::ViCharSearch(n, cs)) => s.edit_move_to(cs, n)?,
+        Cmd::RevertLine => s.edit_revert_line()?,
+        Cmd::GlobExpandWord => s.edit_glob_expand_word()?,
         Cmd::Undo(n) => {
             if s.changes.borrow_mut().undo(&mut s.line, n) {
                 s.refresh_line()?;
             }
         }
+        Cmd::Redo(n) => {
+            let mut redone = false;
+            for _ in 0..n {
+                if !s.changes.borrow_mut().redo(&mut s.line) {
+                    break;
+                }
+                redone = true;
+            }
+            if redone {
+                s.refresh_line()?;
+            }
+        }
         Cmd::Dedent(mvt) => {
             s.edit_indent(&mvt, config.indent_size(), true)?;
         }
@@ -1,8 +1,23 @@
 //! Completion API
 use std::borrow::Cow::{self, Borrowed, Owned};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::{self, Path};
+use std::io::Read;
+use std::ops::Range;
+use std::path::{self, Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::config::CompletionType;
+use crate::fuzzy::fuzzy_match;
+use crate::highlight::{Highlighter, LsColors};
+use crate::history::History;
 use crate::line_buffer::LineBuffer;
 use crate::{Context, Result};
 use memchr::memchr;
@@ -13,6 +28,11 @@ pub trait Candidate {
     fn display(&self) -> &str;
     /// Text to insert in line.
     fn replacement(&self) -> &str;
+    /// Text to display next to the candidate when listing alternatives
+    /// (for a subcommand or a flag, for example).
+    fn description(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl Candidate for String {
@@ -47,6 +67,7 @@ impl Candidate for &'_ str {
 }
 
 /// Completion candidate pair
+#[derive(Clone)]
 pub struct Pair {
     /// Text to display when listing alternatives.
     pub display: String,
@@ -64,6 +85,121 @@ impl Candidate for Pair {
     }
 }
 
+/// Completion candidate with a description
+#[derive(Clone)]
+pub struct DescribedPair {
+    /// Text to display when listing alternatives.
+    pub display: String,
+    /// Text to insert in line.
+    pub replacement: String,
+    /// Text to display next to the candidate when listing alternatives.
+    pub description: Option<String>,
+}
+
+impl Candidate for DescribedPair {
+    fn display(&self) -> &str {
+        self.display.as_str()
+    }
+
+    fn replacement(&self) -> &str {
+        self.replacement.as_str()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+/// Completion result: start position and candidates, with options
+/// controlling how they are applied (see [`Completer::completion`]).
+pub struct Completion<C> {
+    /// Start position of the partial word to be completed
+    pub start: usize,
+    /// Completion candidates
+    pub candidates: Vec<C>,
+    suffix: Option<char>,
+    completion_type: Option<CompletionType>,
+    retrigger: bool,
+}
+
+impl<C> Completion<C> {
+    /// Constructor: no suffix, configured completion type, no re-trigger.
+    #[must_use]
+    pub fn new(start: usize, candidates: Vec<C>) -> Self {
+        Self {
+            start,
+            candidates,
+            suffix: None,
+            completion_type: None,
+            retrigger: false,
+        }
+    }
+
+    /// Append `suffix` (usually a space) after the candidate when it is the
+    /// only one.
+    #[must_use]
+    pub fn with_suffix(mut self, suffix: char) -> Self {
+        self.suffix = Some(suffix);
+        self
+    }
+
+    /// Use `completion_type` instead of the configured one for this call.
+    #[must_use]
+    pub fn with_completion_type(mut self, completion_type: CompletionType) -> Self {
+        self.completion_type = Some(completion_type);
+        self
+    }
+
+    /// When the only candidate has been inserted (like a directory), complete
+    /// again immediately.
+    #[must_use]
+    pub fn with_retrigger(mut self, retrigger: bool) -> Self {
+        self.retrigger = retrigger;
+        self
+    }
+
+    /// Appended after the only candidate
+    #[must_use]
+    pub fn suffix(&self) -> Option<char> {
+        self.suffix
+    }
+
+    /// Completion type for this call, `None` for the configured one
+    #[must_use]
+    pub fn completion_type(&self) -> Option<CompletionType> {
+        self.completion_type
+    }
+
+    /// Whether completion is re-triggered after the only candidate is
+    /// inserted
+    #[must_use]
+    pub fn retrigger(&self) -> bool {
+        self.retrigger
+    }
+
+    /// Same options with the candidates converted by `f`
+    fn map_candidates<D>(self, f: impl FnMut(C) -> D) -> Completion<D> {
+        Completion {
+            start: self.start,
+            candidates: self.candidates.into_iter().map(f).collect(),
+            suffix: self.suffix,
+            completion_type: self.completion_type,
+            retrigger: self.retrigger,
+        }
+    }
+}
+
+impl<C: Candidate> Completion<C> {
+    /// Text inserted for the only candidate: its replacement and the suffix
+    pub(crate) fn unique_replacement(&self) -> Cow<'_, str> {
+        let replacement = self.candidates[0].replacement();
+        match self.suffix {
+            Some(suffix) => Owned(format!("{}{}", replacement, suffix)),
+            None => Borrowed(replacement),
+        }
+    }
+}
+
 // TODO: let the implementers customize how the candidate(s) are displayed
 // https://github.com/kkawakam/rustyline/issues/302
 
@@ -88,11 +224,60 @@ pub trait Completer {
         let _ = (line, pos, ctx);
         Ok((0, Vec::with_capacity(0)))
     }
+    /// Like [`complete`](Completer::complete) but the result may also
+    /// control the suffix appended to the only candidate, the completion
+    /// type used for this call and whether completion is re-triggered.
+    ///
+    /// By default, wraps the result of [`complete`](Completer::complete)
+    /// without any option.
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<Self::Candidate>> {
+        let (start, candidates) = self.complete(line, pos, ctx)?;
+        Ok(Completion::new(start, candidates))
+    }
+    /// Starts computing the completion candidates in a background thread,
+    /// for slow completers.
+    ///
+    /// While the completion is pending, a spinner is displayed and keys are
+    /// still read: any key but `Complete` cancels the completion and is
+    /// processed as usual.
+    ///
+    /// By default, returns `None`: [`complete`](Completer::complete) is
+    /// called synchronously. See [`BackgroundCompleter`].
+    fn complete_in_background(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Option<PendingCompletion<Self::Candidate>> {
+        let _ = (line, pos, ctx);
+        None
+    }
     /// Updates the edited `line` with the `elected` candidate.
     fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
         let end = line.pos();
         line.replace(start..end, elected);
     }
+    /// Updates the edited `line` with the `elected` text coming from
+    /// `candidate`: its replacement (maybe followed by a suffix) or, when
+    /// several candidates are listed, their common prefix (then `candidate`
+    /// is the first one).
+    ///
+    /// By default, calls [`update`](Completer::update).
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &Self::Candidate,
+        elected: &str,
+    ) {
+        let _ = candidate;
+        self.update(line, start, elected);
+    }
 }
 
 impl Completer for () {
@@ -115,9 +300,37 @@ impl<'c, C: ?Sized + Completer> Completer for &'c C {
         (**self).complete(line, pos, ctx)
     }
 
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<Self::Candidate>> {
+        (**self).completion(line, pos, ctx)
+    }
+
+    fn complete_in_background(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Option<PendingCompletion<Self::Candidate>> {
+        (**self).complete_in_background(line, pos, ctx)
+    }
+
     fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
         (**self).update(line, start, elected);
     }
+
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &Self::Candidate,
+        elected: &str,
+    ) {
+        (**self).update_candidate(line, start, candidate, elected);
+    }
 }
 macro_rules! box_completer {
     ($($id: ident)*) => {
@@ -128,9 +341,18 @@ macro_rules! box_completer {
                 fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<(usize, Vec<Self::Candidate>)> {
                     (**self).complete(line, pos, ctx)
                 }
+                fn completion(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Completion<Self::Candidate>> {
+                    (**self).completion(line, pos, ctx)
+                }
+                fn complete_in_background(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<PendingCompletion<Self::Candidate>> {
+                    (**self).complete_in_background(line, pos, ctx)
+                }
                 fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
                     (**self).update(line, start, elected)
                 }
+                fn update_candidate(&self, line: &mut LineBuffer, start: usize, candidate: &Self::Candidate, elected: &str) {
+                    (**self).update_candidate(line, start, candidate, elected)
+                }
             }
         )*
     }
@@ -140,144 +362,1144 @@ use std::rc::Rc;
 use std::sync::Arc;
 box_completer! { Box Rc Arc }
 
-/// A `Completer` for file and folder names.
-pub struct FilenameCompleter {
-    break_chars: &'static [u8],
-    double_quotes_special_chars: &'static [u8],
+/// Candidate of a [`FallbackCompleter`]: tells which completer produced it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FallbackCandidate<A, B> {
+    /// Candidate of the first completer
+    First(A),
+    /// Candidate of the second completer
+    Second(B),
 }
 
-const DOUBLE_QUOTES_ESCAPE_CHAR: Option<char> = Some('\\');
+impl<A: Candidate, B: Candidate> Candidate for FallbackCandidate<A, B> {
+    fn display(&self) -> &str {
+        match self {
+            FallbackCandidate::First(c) => c.display(),
+            FallbackCandidate::Second(c) => c.display(),
+        }
+    }
 
-cfg_if::cfg_if! {
-    if #[cfg(unix)] {
-        // rl_basic_word_break_characters, rl_completer_word_break_characters
-        const DEFAULT_BREAK_CHARS: [u8; 18] = [
-            b' ', b'\t', b'\n', b'"', b'\\', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&',
-            b'{', b'(', b'\0',
-        ];
-        const ESCAPE_CHAR: Option<char> = Some('\\');
-        // In double quotes, not all break_chars need to be escaped
-        // https://www.gnu.org/software/bash/manual/html_node/Double-Quotes.html
-        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 4] = [b'"', b'$', b'\\', b'`'];
-    } else if #[cfg(windows)] {
-        // Remove \ to make file completion works on windows
-        const DEFAULT_BREAK_CHARS: [u8; 17] = [
-            b' ', b'\t', b'\n', b'"', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&', b'{',
-            b'(', b'\0',
-        ];
-        const ESCAPE_CHAR: Option<char> = None;
-        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 1] = [b'"']; // TODO Validate: only '"' ?
-    } else if #[cfg(target_arch = "wasm32")] {
-        const DEFAULT_BREAK_CHARS: [u8; 0] = [];
-        const ESCAPE_CHAR: Option<char> = None;
-        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 0] = [];
+    fn replacement(&self) -> &str {
+        match self {
+            FallbackCandidate::First(c) => c.replacement(),
+            FallbackCandidate::Second(c) => c.replacement(),
+        }
+    }
+
+    fn description(&self) -> Option<&str> {
+        match self {
+            FallbackCandidate::First(c) => c.description(),
+            FallbackCandidate::Second(c) => c.description(),
+        }
     }
 }
 
-/// Kind of quote.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub enum Quote {
-    /// Double quote: `"`
-    Double,
-    /// Single quote: `'`
-    Single,
-    /// No quote
-    None,
+/// A `Completer` which delegates to the second completer only when the first
+/// one returns no candidate.
+///
+/// Can be nested to try more than two completers:
+/// `FallbackCompleter::new(commands, FallbackCompleter::new(files, words))`.
+///
+/// The line is updated by the completer which produced the elected
+/// candidate (see [`Completer::update_candidate`]).
+pub struct FallbackCompleter<A, B> {
+    first: A,
+    second: B,
 }
 
-impl FilenameCompleter {
+impl<A, B> FallbackCompleter<A, B> {
+    /// Constructor
+    #[must_use]
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+impl<A, B> Completer for FallbackCompleter<A, B>
+where
+    A: Completer,
+    B: Completer,
+{
+    type Candidate = FallbackCandidate<A::Candidate, B::Candidate>;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        let completion = self.completion(line, pos, ctx)?;
+        Ok((completion.start, completion.candidates))
+    }
+
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<Self::Candidate>> {
+        let completion = self.first.completion(line, pos, ctx)?;
+        if completion.candidates.is_empty() {
+            let completion = self.second.completion(line, pos, ctx)?;
+            Ok(completion.map_candidates(FallbackCandidate::Second))
+        } else {
+            Ok(completion.map_candidates(FallbackCandidate::First))
+        }
+    }
+
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &Self::Candidate,
+        elected: &str,
+    ) {
+        match candidate {
+            FallbackCandidate::First(c) => self.first.update_candidate(line, start, c, elected),
+            FallbackCandidate::Second(c) => self.second.update_candidate(line, start, c, elected),
+        }
+    }
+}
+
+/// Candidate of a [`MergeCompleter`], with the index of the completer which
+/// produced it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeCandidate<C> {
+    /// Candidate of the completer
+    pub candidate: C,
+    /// Index of the completer, in the order they were added
+    pub completer: usize,
+}
+
+impl<C: Candidate> Candidate for MergeCandidate<C> {
+    fn display(&self) -> &str {
+        self.candidate.display()
+    }
+
+    fn replacement(&self) -> &str {
+        self.candidate.replacement()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.candidate.description()
+    }
+}
+
+/// A `Completer` which merges the candidates of several completers.
+///
+/// The start position is given by the first completer returning candidates:
+/// candidates from completers with a different start position are ignored,
+/// as are duplicated replacements.
+///
+/// The line is updated by the completer which produced the elected
+/// candidate (see [`Completer::update_candidate`]).
+pub struct MergeCompleter<C: Candidate> {
+    completers: Vec<Box<dyn Completer<Candidate = C>>>,
+}
+
+impl<C: Candidate> MergeCompleter<C> {
     /// Constructor
     #[must_use]
     pub fn new() -> Self {
         Self {
-            break_chars: &DEFAULT_BREAK_CHARS,
-            double_quotes_special_chars: &DOUBLE_QUOTES_SPECIAL_CHARS,
+            completers: Vec::new(),
         }
     }
 
-    /// Takes the currently edited `line` with the cursor `pos`ition and
-    /// returns the start position and the completion candidates for the
-    /// partial path to be completed.
-    pub fn complete_path(&self, line: &str, pos: usize) -> Result<(usize, Vec<Pair>)> {
-        let (start, path, esc_char, break_chars, quote) =
-            if let Some((idx, quote)) = find_unclosed_quote(&line[..pos]) {
-                let start = idx + 1;
-                if quote == Quote::Double {
-                    (
-                        start,
-                        unescape(&line[start..pos], DOUBLE_QUOTES_ESCAPE_CHAR),
-                        DOUBLE_QUOTES_ESCAPE_CHAR,
-                        &self.double_quotes_special_chars,
-                        quote,
-                    )
-                } else {
-                    (
-                        start,
-                        Borrowed(&line[start..pos]),
-                        None,
-                        &self.break_chars,
-                        quote,
-                    )
-                }
-            } else {
-                let (start, path) = extract_word(line, pos, ESCAPE_CHAR, self.break_chars);
-                let path = unescape(path, ESCAPE_CHAR);
-                (start, path, ESCAPE_CHAR, &self.break_chars, Quote::None)
-            };
-        let mut matches = filename_complete(&path, esc_char, break_chars, quote);
-        #[allow(clippy::unnecessary_sort_by)]
-        matches.sort_by(|a, b| a.display().cmp(b.display()));
-        Ok((start, matches))
+    /// Add a completer whose candidates are merged with the previous ones
+    #[must_use]
+    pub fn with_completer<T: Completer<Candidate = C> + 'static>(mut self, completer: T) -> Self {
+        self.completers.push(Box::new(completer));
+        self
     }
 }
 
-impl Default for FilenameCompleter {
+impl<C: Candidate> Default for MergeCompleter<C> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl Completer for FilenameCompleter {
-    type Candidate = Pair;
+impl<C: Candidate> Completer for MergeCompleter<C> {
+    type Candidate = MergeCandidate<C>;
 
-    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
-        self.complete_path(line, pos)
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        let completion = self.completion(line, pos, ctx)?;
+        Ok((completion.start, completion.candidates))
     }
-}
 
-/// Remove escape char
-#[must_use]
-pub fn unescape(input: &str, esc_char: Option<char>) -> Cow<'_, str> {
-    let esc_char = if let Some(c) = esc_char {
-        c
-    } else {
-        return Borrowed(input);
-    };
-    if !input.chars().any(|c| c == esc_char) {
-        return Borrowed(input);
-    }
-    let mut result = String::with_capacity(input.len());
-    let mut chars = input.chars();
-    while let Some(ch) = chars.next() {
-        if ch == esc_char {
-            if let Some(ch) = chars.next() {
-                if cfg!(windows) && ch != '"' {
-                    // TODO Validate: only '"' ?
-                    result.push(esc_char);
+    /// The options (suffix, completion type, ...) are the ones of the first
+    /// completer returning candidates.
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<Self::Candidate>> {
+        let mut merged: Option<Completion<Self::Candidate>> = None;
+        for (i, completer) in self.completers.iter().enumerate() {
+            let completion = completer.completion(line, pos, ctx)?;
+            if completion.candidates.is_empty()
+                || matches!(merged, Some(ref merged) if merged.start != completion.start)
+            {
+                continue;
+            }
+            let mut completion = completion.map_candidates(|candidate| MergeCandidate {
+                candidate,
+                completer: i,
+            });
+            let candidates = std::mem::take(&mut completion.candidates);
+            let merged = merged.get_or_insert(completion);
+            for candidate in candidates {
+                if !merged
+                    .candidates
+                    .iter()
+                    .any(|c| c.replacement() == candidate.replacement())
+                {
+                    merged.candidates.push(candidate);
                 }
-                result.push(ch);
-            } else if cfg!(windows) {
-                result.push(ch);
             }
-        } else {
-            result.push(ch);
         }
+        Ok(merged.unwrap_or_else(|| Completion::new(0, Vec::new())))
     }
-    Owned(result)
-}
 
-/// Escape any `break_chars` in `input` string with `esc_char`.
-/// For example, '/User Information' becomes '/User\ Information'
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &Self::Candidate,
+        elected: &str,
+    ) {
+        match self.completers.get(candidate.completer) {
+            Some(completer) => {
+                completer.update_candidate(line, start, &candidate.candidate, elected)
+            }
+            None => self.update(line, start, elected),
+        }
+    }
+}
+
+/// A `Completer` which filters and ranks the candidates of another completer
+/// with [`fuzzy_match`]: the word being completed (from the start position
+/// returned by the wrapped completer to the cursor) must be a subsequence of
+/// the candidates display, best matches first.
+///
+/// The wrapped completer should return all the candidates for the current
+/// word, without filtering them itself (by prefix).
+///
+/// Matched characters are highlighted by
+/// [`Highlighter::highlight_candidate`]: delegate to this completer in your
+/// `Helper`.
+pub struct FuzzyCompleter<C> {
+    completer: C,
+    pattern: Mutex<String>, // last completed word
+}
+
+impl<C> FuzzyCompleter<C> {
+    /// Constructor
+    #[must_use]
+    pub fn new(completer: C) -> Self {
+        Self {
+            completer,
+            pattern: Mutex::new(String::new()),
+        }
+    }
+}
+
+impl<C: Completer> Completer for FuzzyCompleter<C> {
+    type Candidate = C::Candidate;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        let completion = self.completion(line, pos, ctx)?;
+        Ok((completion.start, completion.candidates))
+    }
+
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<Self::Candidate>> {
+        let mut completion = self.completer.completion(line, pos, ctx)?;
+        let pattern = line.get(completion.start..pos).unwrap_or_default();
+        let mut matches: Vec<(i64, Self::Candidate)> = completion
+            .candidates
+            .drain(..)
+            .filter_map(|c| fuzzy_match(pattern, c.display()).map(|m| (m.score, c)))
+            .collect();
+        // stable: same order as the wrapped completer for equal scores
+        matches.sort_by_key(|(score, _)| Reverse(*score));
+        *self.pattern.lock().unwrap() = pattern.to_owned();
+        completion.candidates = matches.into_iter().map(|(_, c)| c).collect();
+        Ok(completion)
+    }
+
+    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+        self.completer.update(line, start, elected);
+    }
+
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &Self::Candidate,
+        elected: &str,
+    ) {
+        self.completer
+            .update_candidate(line, start, candidate, elected);
+    }
+}
+
+impl<C> Highlighter for FuzzyCompleter<C> {
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        _completion: CompletionType,
+    ) -> Cow<'c, str> {
+        let positions = match fuzzy_match(&self.pattern.lock().unwrap(), candidate) {
+            Some(m) if !m.positions.is_empty() => m.positions,
+            _ => return Borrowed(candidate),
+        };
+        let mut highlighted = String::with_capacity(candidate.len() + positions.len() * 9);
+        for (i, c) in candidate.char_indices() {
+            if positions.binary_search(&i).is_ok() {
+                highlighted.push_str("\x1b[1m");
+                highlighted.push(c);
+                highlighted.push_str("\x1b[22m");
+            } else {
+                highlighted.push(c);
+            }
+        }
+        Owned(highlighted)
+    }
+}
+
+/// A `Completer` for the words of the history entries (like bash
+/// `dynamic-complete-history`), most recent first.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HistoryCompleter;
+
+impl HistoryCompleter {
+    /// Constructor
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Completer for HistoryCompleter {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<(usize, Vec<String>)> {
+        let start = word_start(line, pos);
+        Ok((start, history_words(ctx.history(), &line[start..pos])))
+    }
+}
+
+/// Start of the (whitespace separated) word before `pos`.
+pub(crate) fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+/// Words of the `history` entries starting with `prefix` (but different),
+/// most recent first, without duplicates.
+pub(crate) fn history_words(history: &History, prefix: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for entry in history.iter().rev() {
+        for word in split_words(entry).into_iter().rev() {
+            if word.len() > prefix.len()
+                && word.starts_with(prefix)
+                && !words.iter().any(|w| w == word)
+            {
+                words.push(word.to_owned());
+            }
+        }
+    }
+    words
+}
+
+/// Completion candidates computed in a background thread
+/// (see [`Completer::complete_in_background`]).
+///
+/// Dropping it cancels the completion.
+pub struct PendingCompletion<C> {
+    receiver: Receiver<Result<Completion<C>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<C> PendingCompletion<C> {
+    /// Creates a pending completion and the sender to be moved into the
+    /// background thread.
+    #[must_use]
+    pub fn new() -> (Self, CompletionSender<C>) {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let sender = CompletionSender {
+            sender,
+            cancelled: Arc::clone(&cancelled),
+        };
+        (
+            Self {
+                receiver,
+                cancelled,
+            },
+            sender,
+        )
+    }
+
+    /// Waits at most `timeout` for the completion.
+    /// A background thread that ends without sending any result means no
+    /// candidate.
+    pub(crate) fn wait(&self, timeout: Duration) -> Option<Result<Completion<C>>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Ok(Completion::new(0, Vec::new()))),
+        }
+    }
+}
+
+impl<C> Drop for PendingCompletion<C> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Sends the result of a [`PendingCompletion`] from the background thread.
+pub struct CompletionSender<C> {
+    sender: Sender<Result<Completion<C>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<C> CompletionSender<C> {
+    /// Tells if the completion has been cancelled (because a key has been
+    /// pressed): slow completers may stop early.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Sends the completion, as returned by [`Completer::completion`]
+    /// (ignored if the completion has been cancelled).
+    pub fn send(self, result: Result<Completion<C>>) {
+        if !self.is_cancelled() {
+            let _ = self.sender.send(result);
+        }
+    }
+}
+
+/// A `Completer` which calls the wrapped completer in a background thread
+/// so that slow completions don't freeze the input: a spinner is displayed
+/// and a key press cancels the completion.
+///
+/// Each completion spawns a thread where
+/// [`Context::history`](crate::Context::history) is empty.
+pub struct BackgroundCompleter<C> {
+    completer: Arc<C>,
+}
+
+impl<C> BackgroundCompleter<C> {
+    /// Constructor
+    pub fn new(completer: C) -> Self {
+        Self {
+            completer: Arc::new(completer),
+        }
+    }
+}
+
+impl<C> Completer for BackgroundCompleter<C>
+where
+    C: Completer + Send + Sync + 'static,
+    C::Candidate: Send + 'static,
+{
+    type Candidate = C::Candidate;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        self.completer.complete(line, pos, ctx)
+    }
+
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<Self::Candidate>> {
+        self.completer.completion(line, pos, ctx)
+    }
+
+    fn complete_in_background(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Option<PendingCompletion<Self::Candidate>> {
+        let (pending, sender) = PendingCompletion::new();
+        let completer = Arc::clone(&self.completer);
+        let line = line.to_owned();
+        thread::Builder::new()
+            .name("completion".to_owned())
+            .spawn(move || {
+                let history = History::new();
+                let ctx = Context::new(&history);
+                sender.send(completer.completion(&line, pos, &ctx));
+            })
+            .ok()?; // fallback to synchronous completion
+        Some(pending)
+    }
+
+    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+        self.completer.update(line, start, elected);
+    }
+
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &Self::Candidate,
+        elected: &str,
+    ) {
+        self.completer
+            .update_candidate(line, start, candidate, elected);
+    }
+}
+
+/// Completions cached by a [`CachingCompleter`], keyed by the current
+/// directory (relative paths), the line up to the start of the word and the
+/// word being completed.
+type CompletionCache<C> = HashMap<(Option<PathBuf>, String, String), (Instant, Completion<C>)>;
+
+/// Tells if a cached candidate is still offered for the typed word (see
+/// [`CachingCompleter::with_matcher`]).
+pub type CandidateMatcher<C> = dyn Fn(&str, &C) -> bool + Send + Sync;
+
+/// A `Completer` which caches the candidates of a slow completer (like file
+/// names over a network file system).
+///
+/// By default, the cache is only used when the same word is completed again.
+/// With a [`matcher`](CachingCompleter::with_matcher), when the word being
+/// completed extends a cached one, the cached candidates are filtered instead
+/// of calling the wrapped completer, unless none of them is longer than the
+/// word. Only the line up to the cursor and the current directory are
+/// considered. Cached candidates expire after [`with_ttl`](CachingCompleter::with_ttl).
+///
+/// Can be wrapped by a [`BackgroundCompleter`] so that cache misses don't
+/// freeze the input.
+pub struct CachingCompleter<C: Completer> {
+    completer: C,
+    ttl: Duration,
+    matcher: Option<Box<CandidateMatcher<C::Candidate>>>,
+    cache: Mutex<CompletionCache<C::Candidate>>,
+}
+
+impl<C: Completer> CachingCompleter<C> {
+    /// Constructor: candidates are cached for 5 seconds.
+    pub fn new(completer: C) -> Self {
+        Self {
+            completer,
+            ttl: Duration::from_secs(5),
+            matcher: None,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Time to live of the cached candidates
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Filter the cached candidates of a shorter word with `matcher`, given
+    /// the typed word and a candidate. It must select the candidates the
+    /// wrapped completer would return (case sensitive prefix, case
+    /// insensitive prefix, fuzzy, ...).
+    ///
+    /// ```
+    /// use rustyline::completion::{CachingCompleter, Candidate, FilenameCompleter};
+    ///
+    /// let completer = CachingCompleter::new(FilenameCompleter::new())
+    ///     .with_matcher(|word, candidate| candidate.replacement().starts_with(word));
+    /// ```
+    #[must_use]
+    pub fn with_matcher<F>(mut self, matcher: F) -> Self
+    where
+        F: Fn(&str, &C::Candidate) -> bool + Send + Sync + 'static,
+    {
+        self.matcher = Some(Box::new(matcher));
+        self
+    }
+
+    /// Discard all cached candidates
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+impl<C> CachingCompleter<C>
+where
+    C: Completer,
+    C::Candidate: Clone,
+{
+    /// Cached completion of the line up to `pos`, if any
+    fn cached(&self, line: &str, pos: usize) -> Option<Completion<C::Candidate>> {
+        let typed = &line[..pos];
+        let cwd = std::env::current_dir().ok();
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (time, _)| time.elapsed() < self.ttl);
+        // the longest cached word extended by the typed one
+        let ((_, prefix, cached_word), (_, completion)) = cache
+            .iter()
+            .filter(|((dir, prefix, word), _)| {
+                *dir == cwd
+                    && typed.starts_with(prefix.as_str())
+                    && typed[prefix.len()..].starts_with(word.as_str())
+            })
+            .max_by_key(|((_, _, word), _)| word.len())?;
+        let word = &typed[prefix.len()..];
+        let candidates = if word == cached_word {
+            completion.candidates.clone()
+        } else {
+            let matcher = self.matcher.as_ref()?;
+            let candidates: Vec<C::Candidate> = completion
+                .candidates
+                .iter()
+                .filter(|c| matcher(word, c))
+                .cloned()
+                .collect();
+            if !candidates
+                .iter()
+                .any(|c| c.replacement().len() > word.len())
+            {
+                return None;
+            }
+            candidates
+        };
+        Some(Completion {
+            start: prefix.len(),
+            candidates,
+            ..*completion
+        })
+    }
+}
+
+impl<C> Completer for CachingCompleter<C>
+where
+    C: Completer,
+    C::Candidate: Clone,
+{
+    type Candidate = C::Candidate;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        let completion = self.completion(line, pos, ctx)?;
+        Ok((completion.start, completion.candidates))
+    }
+
+    fn completion(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Completion<Self::Candidate>> {
+        if let Some(completion) = self.cached(line, pos) {
+            return Ok(completion);
+        }
+        let completion = self.completer.completion(line, pos, ctx)?;
+        let start = completion.start;
+        if start <= pos {
+            let key = (
+                std::env::current_dir().ok(),
+                line[..start].to_owned(),
+                line[start..pos].to_owned(),
+            );
+            let cached = Completion {
+                start,
+                candidates: completion.candidates.clone(),
+                ..completion
+            };
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(key, (Instant::now(), cached));
+        }
+        Ok(completion)
+    }
+
+    /// On a cache miss, delegates to the wrapped completer: the candidates
+    /// computed in the background are not cached.
+    fn complete_in_background(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Option<PendingCompletion<Self::Candidate>> {
+        if self.cached(line, pos).is_some() {
+            return None; // synchronously served by `completion`
+        }
+        self.completer.complete_in_background(line, pos, ctx)
+    }
+
+    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+        self.completer.update(line, start, elected);
+    }
+
+    fn update_candidate(
+        &self,
+        line: &mut LineBuffer,
+        start: usize,
+        candidate: &Self::Candidate,
+        elected: &str,
+    ) {
+        self.completer
+            .update_candidate(line, start, candidate, elected);
+    }
+}
+
+/// A `Completer` for file and folder names.
+///
+/// Use [`FilenameCompleter::builder`] to restrict the candidates.
+#[derive(Clone, Debug)]
+pub struct FilenameCompleter {
+    break_chars: &'static [u8],
+    double_quotes_special_chars: &'static [u8],
+    file_type: FileType,
+    extensions: Vec<String>,
+    globs: Vec<String>,
+    hide_dotfiles: bool,
+    case_insensitive: bool,
+    map_case: bool,
+    mark_directories: bool,
+    ls_colors: Option<LsColors>,
+    file_kinds: FileKinds, // of the last candidates, for highlighting
+}
+
+/// File kinds of the candidates, by display
+#[derive(Debug, Default)]
+struct FileKinds(Mutex<HashMap<String, FileKind>>);
+
+impl Clone for FileKinds {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+/// Kind of file system entry, used to color file name candidates
+/// (see [`LsColors`]).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FileKind {
+    /// Directory
+    Directory,
+    /// Symbolic link
+    Symlink,
+    /// Symbolic link to a nonexistent file
+    OrphanSymlink,
+    /// Named pipe
+    Fifo,
+    /// Socket
+    Socket,
+    /// Block device
+    BlockDevice,
+    /// Character device
+    CharDevice,
+    /// Executable regular file
+    Executable,
+    /// Regular file
+    File,
+}
+
+impl FileKind {
+    /// Kind of the entry at `path` (symbolic links are not followed).
+    #[must_use]
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        let file_type = metadata.file_type();
+        Some(if file_type.is_symlink() {
+            if path.exists() {
+                FileKind::Symlink
+            } else {
+                FileKind::OrphanSymlink
+            }
+        } else if file_type.is_dir() {
+            FileKind::Directory
+        } else if let Some(kind) = special_file_kind(&file_type) {
+            kind
+        } else if is_executable(
+            &path.file_name().unwrap_or_default().to_string_lossy(),
+            &metadata,
+        ) {
+            FileKind::Executable
+        } else {
+            FileKind::File
+        })
+    }
+}
+
+#[cfg(unix)]
+fn special_file_kind(file_type: &fs::FileType) -> Option<FileKind> {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_fifo() {
+        Some(FileKind::Fifo)
+    } else if file_type.is_socket() {
+        Some(FileKind::Socket)
+    } else if file_type.is_block_device() {
+        Some(FileKind::BlockDevice)
+    } else if file_type.is_char_device() {
+        Some(FileKind::CharDevice)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_kind(_file_type: &fs::FileType) -> Option<FileKind> {
+    None
+}
+
+/// Kind of files offered by a [`FilenameCompleter`].
+///
+/// Directories are always offered so that the user can navigate into them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FileType {
+    /// Any file or directory
+    Any,
+    /// Directories only
+    Directory,
+    /// Regular files (and directories)
+    File,
+    /// Executable files (and directories)
+    Executable,
+}
+
+const DOUBLE_QUOTES_ESCAPE_CHAR: Option<char> = Some('\\');
+
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        // rl_basic_word_break_characters, rl_completer_word_break_characters
+        const DEFAULT_BREAK_CHARS: [u8; 18] = [
+            b' ', b'\t', b'\n', b'"', b'\\', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&',
+            b'{', b'(', b'\0',
+        ];
+        const ESCAPE_CHAR: Option<char> = Some('\\');
+        // In double quotes, not all break_chars need to be escaped
+        // https://www.gnu.org/software/bash/manual/html_node/Double-Quotes.html
+        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 4] = [b'"', b'$', b'\\', b'`'];
+    } else if #[cfg(windows)] {
+        // Remove \ to make file completion works on windows
+        const DEFAULT_BREAK_CHARS: [u8; 17] = [
+            b' ', b'\t', b'\n', b'"', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&', b'{',
+            b'(', b'\0',
+        ];
+        const ESCAPE_CHAR: Option<char> = None;
+        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 1] = [b'"']; // TODO Validate: only '"' ?
+    } else if #[cfg(target_arch = "wasm32")] {
+        const DEFAULT_BREAK_CHARS: [u8; 0] = [];
+        const ESCAPE_CHAR: Option<char> = None;
+        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 0] = [];
+    }
+}
+
+/// Kind of quote.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Quote {
+    /// Double quote: `"`
+    Double,
+    /// Single quote: `'`
+    Single,
+    /// No quote
+    None,
+}
+
+impl FilenameCompleter {
+    /// Constructor
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            break_chars: &DEFAULT_BREAK_CHARS,
+            double_quotes_special_chars: &DOUBLE_QUOTES_SPECIAL_CHARS,
+            file_type: FileType::Any,
+            extensions: Vec::new(),
+            globs: Vec::new(),
+            hide_dotfiles: false,
+            case_insensitive: cfg!(any(windows, target_os = "macos")),
+            map_case: false,
+            mark_directories: true,
+            ls_colors: None,
+            file_kinds: FileKinds::default(),
+        }
+    }
+
+    /// Returns a `FilenameCompleter` builder.
+    #[must_use]
+    pub fn builder() -> FilenameCompleterBuilder {
+        FilenameCompleterBuilder::new()
+    }
+
+    /// Tell if the entry named `name` should be offered.
+    fn accept(&self, name: &str, metadata: &fs::Metadata) -> bool {
+        if metadata.is_dir() {
+            return true;
+        }
+        let file_type = match self.file_type {
+            FileType::Any => true,
+            FileType::Directory => false,
+            FileType::File => metadata.is_file(),
+            FileType::Executable => metadata.is_file() && is_executable(name, metadata),
+        };
+        if !file_type {
+            return false;
+        }
+        if self.extensions.is_empty() && self.globs.is_empty() {
+            return true;
+        }
+        let name = normalize(name, self.case_insensitive);
+        let extension = Path::new(name.as_ref())
+            .extension()
+            .and_then(|ext| ext.to_str());
+        self.extensions
+            .iter()
+            .any(|ext| extension == Some(normalize(ext, self.case_insensitive).as_ref()))
+            || self
+                .globs
+                .iter()
+                .any(|glob| glob_match(&normalize(glob, self.case_insensitive), &name))
+    }
+
+    /// File name as compared with the typed one
+    fn normalize<'s>(&self, name: &'s str) -> Cow<'s, str> {
+        let name = normalize(name, self.case_insensitive);
+        if self.map_case && name.contains('-') {
+            Owned(name.replace('-', "_"))
+        } else {
+            name
+        }
+    }
+
+    /// Takes the currently edited `line` with the cursor `pos`ition and
+    /// returns the start position and the completion candidates for the
+    /// partial path to be completed.
+    pub fn complete_path(&self, line: &str, pos: usize) -> Result<(usize, Vec<Pair>)> {
+        let (start, path, esc_char, break_chars, quote) =
+            if let Some((idx, quote)) = find_unclosed_quote(&line[..pos]) {
+                let start = idx + 1;
+                if quote == Quote::Double {
+                    (
+                        start,
+                        unescape(&line[start..pos], DOUBLE_QUOTES_ESCAPE_CHAR),
+                        DOUBLE_QUOTES_ESCAPE_CHAR,
+                        &self.double_quotes_special_chars,
+                        quote,
+                    )
+                } else {
+                    (
+                        start,
+                        Borrowed(&line[start..pos]),
+                        None,
+                        &self.break_chars,
+                        quote,
+                    )
+                }
+            } else {
+                let (start, _) = extract_word(line, pos, ESCAPE_CHAR, self.break_chars);
+                let start = var_ref_start(line, start, self.break_chars);
+                let path = unescape(&line[start..pos], ESCAPE_CHAR);
+                (start, path, ESCAPE_CHAR, &self.break_chars, Quote::None)
+            };
+        let mut matches = filename_complete(self, &path, esc_char, break_chars, quote);
+        #[allow(clippy::unnecessary_sort_by)]
+        matches.sort_by(|a, b| a.display().cmp(b.display()));
+        Ok((start, matches))
+    }
+}
+
+/// Colors the candidates of the last completion according to `LS_COLORS`
+/// when [`FilenameCompleterBuilder::ls_colors`] is set: delegate to this
+/// completer in your [`Highlighter::highlight_candidate`].
+impl Highlighter for FilenameCompleter {
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        _completion: CompletionType,
+    ) -> Cow<'c, str> {
+        match self.ls_colors {
+            Some(ref ls_colors) => {
+                let kind = self.file_kinds.0.lock().unwrap().get(candidate).copied();
+                ls_colors.paint(candidate, kind)
+            }
+            None => Borrowed(candidate),
+        }
+    }
+}
+
+impl Default for FilenameCompleter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `FilenameCompleter` builder
+#[derive(Clone, Debug, Default)]
+pub struct FilenameCompleterBuilder {
+    p: FilenameCompleter,
+}
+
+impl FilenameCompleterBuilder {
+    /// Returns a `FilenameCompleter` builder.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            p: FilenameCompleter::new(),
+        }
+    }
+
+    /// Kind of files to offer.
+    ///
+    /// By default, it's [`FileType::Any`].
+    #[must_use]
+    pub fn file_type(mut self, file_type: FileType) -> Self {
+        self.p.file_type = file_type;
+        self
+    }
+
+    /// Only offer files with the given `extension` (`toml` or `.toml`).
+    /// May be called multiple times.
+    ///
+    /// By default, files are not filtered by extension.
+    #[must_use]
+    pub fn extension(mut self, extension: &str) -> Self {
+        let extension = extension.strip_prefix('.').unwrap_or(extension);
+        self.p.extensions.push(extension.to_owned());
+        self
+    }
+
+    /// Only offer files whose name matches the `glob` pattern (`*` matches
+    /// any sequence of characters, `?` any character, like `*.toml`).
+    /// May be called multiple times.
+    ///
+    /// By default, files are not filtered by name.
+    #[must_use]
+    pub fn glob(mut self, glob: &str) -> Self {
+        self.p.globs.push(glob.to_owned());
+        self
+    }
+
+    /// Only offer dotfiles when the prefix to complete starts with `.`.
+    ///
+    /// By default, dotfiles are always offered.
+    #[must_use]
+    pub fn hide_dotfiles(mut self, yes: bool) -> Self {
+        self.p.hide_dotfiles = yes;
+        self
+    }
+
+    /// Case insensitive matching of file names.
+    ///
+    /// By default, it's enabled on Windows and macOS only.
+    #[must_use]
+    pub fn case_insensitive(mut self, yes: bool) -> Self {
+        self.p.case_insensitive = yes;
+        self
+    }
+
+    /// Hyphens and underscores are equivalent when matching file names (like
+    /// readline `completion-map-case`).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn map_case(mut self, yes: bool) -> Self {
+        self.p.map_case = yes;
+        self
+    }
+
+    /// Color the candidates according to `ls_colors` (see
+    /// [`FilenameCompleter`]'s `Highlighter` implementation).
+    ///
+    /// By default, candidates are not colored.
+    #[must_use]
+    pub fn ls_colors(mut self, ls_colors: LsColors) -> Self {
+        self.p.ls_colors = Some(ls_colors);
+        self
+    }
+
+    /// Append a path separator to directories.
+    ///
+    /// By default, it's enabled.
+    #[must_use]
+    pub fn mark_directories(mut self, yes: bool) -> Self {
+        self.p.mark_directories = yes;
+        self
+    }
+
+    /// Builds a `FilenameCompleter` with the settings specified so far.
+    #[must_use]
+    pub fn build(self) -> FilenameCompleter {
+        self.p
+    }
+}
+
+impl Completer for FilenameCompleter {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
+        self.complete_path(line, pos)
+    }
+}
+
+/// Remove escape char
+#[must_use]
+pub fn unescape(input: &str, esc_char: Option<char>) -> Cow<'_, str> {
+    let esc_char = if let Some(c) = esc_char {
+        c
+    } else {
+        return Borrowed(input);
+    };
+    if !input.chars().any(|c| c == esc_char) {
+        return Borrowed(input);
+    }
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(ch) = chars.next() {
+        if ch == esc_char {
+            if let Some(ch) = chars.next() {
+                if cfg!(windows) && ch != '"' {
+                    // TODO Validate: only '"' ?
+                    result.push(esc_char);
+                }
+                result.push(ch);
+            } else if cfg!(windows) {
+                result.push(ch);
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    Owned(result)
+}
+
+/// Escape any `break_chars` in `input` string with `esc_char`.
+/// For example, '/User Information' becomes '/User\ Information'
 /// when space is a breaking char and '\\' the escape char.
 #[must_use]
 pub fn escape(
@@ -287,248 +1509,1423 @@ pub fn escape(
     quote: Quote,
 ) -> String {
     if quote == Quote::Single {
-        return input; // no escape in single quotes
+        return input; // no escape in single quotes
+    }
+    let n = input
+        .bytes()
+        .filter(|b| memchr(*b, break_chars).is_some())
+        .count();
+    if n == 0 {
+        return input; // no need to escape
+    }
+    let esc_char = if let Some(c) = esc_char {
+        c
+    } else {
+        if cfg!(windows) && quote == Quote::None {
+            input.insert(0, '"'); // force double quote
+            return input;
+        }
+        return input;
+    };
+    let mut result = String::with_capacity(input.len() + n);
+
+    for c in input.chars() {
+        if c.is_ascii() && memchr(c as u8, break_chars).is_some() {
+            result.push(esc_char);
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn filename_complete(
+    completer: &FilenameCompleter,
+    path: &str,
+    esc_char: Option<char>,
+    break_chars: &[u8],
+    quote: Quote,
+) -> Vec<Pair> {
+    use std::env::current_dir;
+
+    let sep = path::MAIN_SEPARATOR;
+    let (dir_name, file_name) = match path.rfind(sep) {
+        Some(idx) => path.split_at(idx + sep.len_utf8()),
+        None => ("", path),
+    };
+
+    let expanded = expand_dir_name(dir_name, quote);
+    let dir_path = Path::new(expanded.as_ref());
+    let dir = if dir_path.is_relative() {
+        if let Ok(cwd) = current_dir() {
+            cwd.join(dir_path)
+        } else {
+            dir_path.to_path_buf()
+        }
+    } else {
+        dir_path.to_path_buf()
+    };
+
+    let mut entries: Vec<Pair> = Vec::new();
+    // only needed to color the candidates
+    let mut file_kinds = completer
+        .ls_colors
+        .as_ref()
+        .map(|_| completer.file_kinds.0.lock().unwrap());
+    if let Some(ref mut file_kinds) = file_kinds {
+        file_kinds.clear();
+    }
+
+    // if dir doesn't exist, then don't offer any completions
+    if !dir.exists() {
+        return entries;
+    }
+
+    // if any of the below IO operations have errors, just ignore them
+    if let Ok(read_dir) = dir.read_dir() {
+        // the user's prefix is kept as typed (`~`, `$HOME`, ...)
+        let dir_name = escape_dir_name(dir_name, esc_char, break_chars, quote);
+        let hide_dotfiles = completer.hide_dotfiles && !file_name.starts_with('.');
+        let file_name = completer.normalize(file_name);
+        for entry in read_dir.flatten() {
+            if let Some(s) = entry.file_name().to_str() {
+                if hide_dotfiles && s.starts_with('.') {
+                    continue;
+                }
+                let ns = completer.normalize(s);
+                if ns.starts_with(file_name.as_ref()) {
+                    if let Ok(metadata) = fs::metadata(entry.path()) {
+                        if !completer.accept(s, &metadata) {
+                            continue;
+                        }
+                        let mut path = String::from(s);
+                        if metadata.is_dir() && completer.mark_directories {
+                            path.push(sep);
+                        }
+                        if let Some(ref mut file_kinds) = file_kinds {
+                            if let Some(kind) = FileKind::of(&entry.path()) {
+                                file_kinds.insert(String::from(s), kind);
+                            }
+                        }
+                        entries.push(Pair {
+                            display: String::from(s),
+                            replacement: dir_name.clone()
+                                + &escape(path, esc_char, break_chars, quote),
+                        });
+                    } // else ignore PermissionDenied
+                }
+            }
+        }
+    }
+    entries
+}
+
+/// Split `s` into literal parts and `$VAR` / `${VAR}` references.
+/// Each part is returned with the variable name if it is a reference.
+fn split_var_refs(s: &str) -> Vec<(&str, Option<&str>)> {
+    fn is_name_char(b: u8) -> bool {
+        b.is_ascii_alphanumeric() || b == b'_'
+    }
+    let bytes = s.as_bytes();
+    let mut parts = Vec::new();
+    let mut literal = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'$' {
+            i += 1;
+            continue;
+        }
+        let (name, end) = if bytes.get(i + 1) == Some(&b'{') {
+            match s[i + 2..].find('}') {
+                Some(len) if len > 0 && s[i + 2..i + 2 + len].bytes().all(is_name_char) => {
+                    (&s[i + 2..i + 2 + len], i + 3 + len)
+                }
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            }
+        } else {
+            let len = bytes[i + 1..]
+                .iter()
+                .take_while(|b| is_name_char(**b))
+                .count();
+            if len == 0 || bytes[i + 1].is_ascii_digit() {
+                i += 1;
+                continue;
+            }
+            (&s[i + 1..i + 1 + len], i + 1 + len)
+        };
+        if literal < i {
+            parts.push((&s[literal..i], None));
+        }
+        parts.push((&s[i..end], Some(name)));
+        literal = end;
+        i = end;
+    }
+    if literal < s.len() {
+        parts.push((&s[literal..], None));
+    }
+    parts
+}
+
+/// Home directory of `user` (the current user if empty).
+fn home_dir(user: &str) -> Option<PathBuf> {
+    if user.is_empty() {
+        #[cfg(feature = "with-dirs")]
+        {
+            dirs_next::home_dir()
+        }
+        #[cfg(not(feature = "with-dirs"))]
+        {
+            None
+        }
+    } else {
+        #[cfg(unix)]
+        {
+            nix::unistd::User::from_name(user)
+                .ok()
+                .flatten()
+                .map(|u| u.dir)
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+}
+
+/// Expand a leading `~` or `~user` and any `$VAR` / `${VAR}` in
+/// `dir_name`, as the shell would do with the specified `quote`:
+/// nothing is expanded in single quotes, only variables in double quotes.
+/// Unknown users and unset variables are left as is.
+fn expand_dir_name(dir_name: &str, quote: Quote) -> Cow<'_, str> {
+    expand_dir_name_with(dir_name, quote, home_dir, |name| std::env::var(name).ok())
+}
+
+/// [`expand_dir_name`] with the specified home directory and variable
+/// lookups.
+fn expand_dir_name_with<'d>(
+    dir_name: &'d str,
+    quote: Quote,
+    home_dir: impl Fn(&str) -> Option<PathBuf>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Cow<'d, str> {
+    if quote == Quote::Single || !(dir_name.starts_with('~') || dir_name.contains('$')) {
+        return Borrowed(dir_name);
+    }
+    let mut result = String::with_capacity(dir_name.len());
+    let mut rest = dir_name;
+    if quote == Quote::None && dir_name.starts_with('~') {
+        // ~[user][/...] (`/` is also a separator on Windows)
+        let end = dir_name
+            .find(['/', path::MAIN_SEPARATOR])
+            .unwrap_or(dir_name.len());
+        if let Some(home) = home_dir(&dir_name[1..end]) {
+            if let Some(home) = home.to_str() {
+                result.push_str(home);
+                rest = &dir_name[end..];
+            }
+        }
+    }
+    for (part, name) in split_var_refs(rest) {
+        match name.and_then(&var) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(part),
+        }
+    }
+    Owned(result)
+}
+
+/// Escape `dir_name` like [`escape`] but leave `$VAR` / `${VAR}` references
+/// untouched so that the shell still expands them.
+fn escape_dir_name(
+    dir_name: &str,
+    esc_char: Option<char>,
+    break_chars: &[u8],
+    quote: Quote,
+) -> String {
+    if quote == Quote::Single {
+        return String::from(dir_name);
     }
-    let n = input
-        .bytes()
-        .filter(|b| memchr(*b, break_chars).is_some())
-        .count();
-    if n == 0 {
-        return input; // no need to escape
+    let mut result = String::with_capacity(dir_name.len());
+    for (part, name) in split_var_refs(dir_name) {
+        if name.is_some() {
+            result.push_str(part);
+        } else {
+            result.push_str(&escape(String::from(part), esc_char, break_chars, quote));
+        }
     }
-    let esc_char = if let Some(c) = esc_char {
-        c
+    result
+}
+
+/// `$` and `{` being break chars, extend the word starting at `start` back
+/// over the `$VAR` or `${VAR}` references (and the text between them).
+fn var_ref_start(line: &str, mut start: usize, break_chars: &[u8]) -> usize {
+    loop {
+        let var_start = prev_var_ref_start(line, start);
+        if var_start == start {
+            return start;
+        }
+        start = extract_word(line, var_start, ESCAPE_CHAR, break_chars).0;
+    }
+}
+
+/// Start of the `$VAR` or `${VAR}` reference just before `start`, if any.
+fn prev_var_ref_start(line: &str, start: usize) -> usize {
+    let var_start = if line[..start].ends_with("${") {
+        start - 2
+    } else if line[..start].ends_with('$') {
+        start - 1
     } else {
-        if cfg!(windows) && quote == Quote::None {
-            input.insert(0, '"'); // force double quote
-            return input;
+        return start;
+    };
+    if line[..var_start].ends_with('\\') {
+        return start; // escaped
+    }
+    match split_var_refs(&line[var_start..]).first() {
+        Some((_, Some(_))) => var_start,
+        _ => start,
+    }
+}
+
+pub(crate) fn normalize(s: &str, case_insensitive: bool) -> Cow<str> {
+    if case_insensitive {
+        Owned(s.to_lowercase())
+    } else {
+        Borrowed(s)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(_name: &str, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(windows)]
+fn is_executable(name: &str, _metadata: &fs::Metadata) -> bool {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ["bat", "cmd", "com", "exe"]
+            .iter()
+            .any(|e| ext.eq_ignore_ascii_case(e)),
+        None => false,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_executable(_name: &str, _metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Match `name` against `pattern` where `*` matches any sequence of
+/// characters and `?` any single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // position of the last `*` in pattern and of the matching char in name
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        let next = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match bracket_match(&pattern[p..], name[n]) {
+                Some((true, len)) => Some(p + len),
+                Some((false, _)) => None,
+                // unclosed bracket: literal `[`
+                None if name[n] == '[' => Some(p + 1),
+                None => None,
+            },
+            Some(&c) if c == name[n] => Some(p + 1),
+            _ => None,
+        };
+        match (next, star) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            (None, Some((sp, sn))) => {
+                // let the last `*` match one more char
+                star = Some((sp, sn + 1));
+                p = sp + 1;
+                n = sn + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match `c` against the bracket expression at the start of `pattern`
+/// (`[abc]`, `[a-z]`, `[!a]` or `[^a]`).
+/// Return whether `c` matches and the length of the expression, or `None` if
+/// the bracket is not closed.
+fn bracket_match(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = matches!(pattern.get(i), Some('!' | '^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match *pattern.get(i)? {
+            ']' if !first => break,
+            lo => {
+                if pattern.get(i + 1) == Some(&'-')
+                    && matches!(pattern.get(i + 2), Some(&hi) if hi != ']')
+                {
+                    matched |= (lo..=pattern[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= lo == c;
+                    i += 1;
+                }
+            }
+        }
+        first = false;
+    }
+    Some((matched != negate, i + 1))
+}
+
+/// Tell if `word` contains glob special characters.
+fn is_glob(word: &str) -> bool {
+    word.contains(['*', '?', '['])
+}
+
+/// Expands the shell glob `pattern` into the matching paths, relative to the
+/// current directory.
+///
+/// `*` matches any string, `?` any character, `[...]` one of the enclosed
+/// characters and `**` any number of directories. Hidden files only match a
+/// pattern component starting with `.`.
+///
+/// Return the sorted paths, or an empty vector when nothing matches.
+#[must_use]
+pub fn glob_expand(pattern: &str) -> Vec<String> {
+    // (path as displayed, path to read)
+    let mut paths = vec![if pattern.starts_with(path::is_separator) {
+        (
+            path::MAIN_SEPARATOR.to_string(),
+            PathBuf::from(path::MAIN_SEPARATOR.to_string()),
+        )
+    } else {
+        (String::new(), PathBuf::from("."))
+    }];
+    let components: Vec<&str> = pattern
+        .split(path::is_separator)
+        .filter(|c| !c.is_empty())
+        .collect();
+    for (i, component) in components.iter().enumerate() {
+        let last = i == components.len() - 1;
+        let mut next = Vec::new();
+        for (display, dir) in paths {
+            if !is_glob(component) {
+                next.push((join(&display, component), dir.join(component)));
+            } else if *component == "**" {
+                if !last {
+                    next.push((display.clone(), dir.clone()));
+                }
+                walk(&display, &dir, !last, &mut next);
+            } else if let Ok(entries) = fs::read_dir(&dir) {
+                for entry in entries.flatten() {
+                    let name = entry.file_name();
+                    let name = match name.to_str() {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    if (!name.starts_with('.') || component.starts_with('.'))
+                        && glob_match(component, name)
+                    {
+                        next.push((join(&display, name), entry.path()));
+                    }
+                }
+            }
+        }
+        paths = next;
+    }
+    let dir_only = pattern.ends_with(path::is_separator);
+    let mut paths: Vec<String> = paths
+        .into_iter()
+        .filter(|(_, path)| match fs::symlink_metadata(path) {
+            Ok(_) if dir_only => path.is_dir(),
+            Ok(_) => true,
+            Err(_) => false,
+        })
+        .map(|(display, _)| {
+            if dir_only {
+                join(&display, "")
+            } else {
+                display
+            }
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() || dir.ends_with(path::is_separator) {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}{}{}", dir, path::MAIN_SEPARATOR, name)
+    }
+}
+
+/// Collect the (non-hidden) descendants of `dir`, only directories if
+/// `dir_only`. Symbolic links are not followed.
+fn walk(display: &str, dir: &Path, dir_only: bool, paths: &mut Vec<(String, PathBuf)>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = match name.to_str() {
+            Some(name) if !name.starts_with('.') => name,
+            _ => continue,
+        };
+        let is_dir = matches!(entry.file_type(), Ok(t) if t.is_dir());
+        let display = join(display, name);
+        if is_dir {
+            walk(&display, &entry.path(), dir_only, paths);
+        }
+        if is_dir || !dir_only {
+            paths.push((display, entry.path()));
+        }
+    }
+}
+
+/// Expands the glob word before the cursor (glob-expand-word).
+/// Return the start of the word and the escaped, space-separated matching
+/// paths, or `None` if the word is not a glob or nothing matches.
+pub(crate) fn glob_expand_word(line: &str, pos: usize) -> Option<(usize, String)> {
+    let (start, word) = extract_word(line, pos, ESCAPE_CHAR, &DEFAULT_BREAK_CHARS);
+    let word = unescape(word, ESCAPE_CHAR);
+    if !is_glob(&word) {
+        return None;
+    }
+    let paths = glob_expand(&word);
+    if paths.is_empty() {
+        return None;
+    }
+    let paths: Vec<String> = paths
+        .into_iter()
+        .map(|path| escape(path, ESCAPE_CHAR, &DEFAULT_BREAK_CHARS, Quote::None))
+        .collect();
+    Some((start, paths.join(" ")))
+}
+
+/// Given a `line` and a cursor `pos`ition,
+/// try to find backward the start of a word.
+/// Return (0, `line[..pos]`) if no break char has been found.
+/// Return the word and its start position (idx, `line[idx..pos]`) otherwise.
+#[must_use]
+pub fn extract_word<'l>(
+    line: &'l str,
+    pos: usize,
+    esc_char: Option<char>,
+    break_chars: &[u8],
+) -> (usize, &'l str) {
+    let line = &line[..pos];
+    if line.is_empty() {
+        return (0, line);
+    }
+    let mut start = None;
+    for (i, c) in line.char_indices().rev() {
+        if let (Some(esc_char), true) = (esc_char, start.is_some()) {
+            if esc_char == c {
+                // escaped break char
+                start = None;
+                continue;
+            }
+            break;
+        }
+        if c.is_ascii() && memchr(c as u8, break_chars).is_some() {
+            start = Some(i + c.len_utf8());
+            if esc_char.is_none() {
+                break;
+            } // else maybe escaped...
+        }
+    }
+
+    match start {
+        Some(start) => (start, &line[start..]),
+        None => (0, line),
+    }
+}
+
+/// Returns the longest common prefix among all `Candidate::replacement()`s.
+pub fn longest_common_prefix<C: Candidate>(candidates: &[C]) -> Option<&str> {
+    if candidates.is_empty() {
+        return None;
+    } else if candidates.len() == 1 {
+        return Some(candidates[0].replacement());
+    }
+    let mut longest_common_prefix = 0;
+    'o: loop {
+        for (i, c1) in candidates.iter().enumerate().take(candidates.len() - 1) {
+            let b1 = c1.replacement().as_bytes();
+            let b2 = candidates[i + 1].replacement().as_bytes();
+            if b1.len() <= longest_common_prefix
+                || b2.len() <= longest_common_prefix
+                || b1[longest_common_prefix] != b2[longest_common_prefix]
+            {
+                break 'o;
+            }
+        }
+        longest_common_prefix += 1;
+    }
+    let candidate = candidates[0].replacement();
+    while !candidate.is_char_boundary(longest_common_prefix) {
+        longest_common_prefix -= 1;
+    }
+    if longest_common_prefix == 0 {
+        return None;
+    }
+    Some(&candidate[0..longest_common_prefix])
+}
+
+/// Returns the longest common prefix among all `Candidate::replacement()`s,
+/// ignoring case (and the difference between `-` and `_` if `map_case`, like
+/// readline `completion-map-case`).
+///
+/// The prefix is taken from the first candidate.
+pub fn longest_common_prefix_ignore_case<C: Candidate>(
+    candidates: &[C],
+    map_case: bool,
+) -> Option<&str> {
+    let fold = |c: char| {
+        if map_case && c == '-' {
+            '_'
+        } else {
+            // simple case folding
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let (first, others) = candidates.split_first()?;
+    let first = first.replacement();
+    let mut len = first.len();
+    for other in others {
+        let mut chars = other.replacement().chars();
+        len = first[..len]
+            .char_indices()
+            .find(|&(_, c1)| !matches!(chars.next(), Some(c2) if fold(c1) == fold(c2)))
+            .map_or(len, |(i, _)| i);
+    }
+    if len == 0 {
+        return None;
+    }
+    Some(&first[..len])
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum ScanMode {
+    DoubleQuote,
+    Escape,
+    EscapeInDoubleQuote,
+    Normal,
+    SingleQuote,
+}
+
+impl ScanMode {
+    /// Scanner state after `char` with the specified `rules`
+    fn next(self, char: char, rules: ShellRules) -> Self {
+        let posix = rules == ShellRules::Posix;
+        match self {
+            ScanMode::DoubleQuote => {
+                if char == '"' {
+                    ScanMode::Normal
+                } else if char == '\\' {
+                    // both windows and unix support escape in double quote
+                    ScanMode::EscapeInDoubleQuote
+                } else {
+                    self
+                }
+            }
+            ScanMode::Escape => ScanMode::Normal,
+            ScanMode::EscapeInDoubleQuote => ScanMode::DoubleQuote,
+            ScanMode::Normal => {
+                if char == '"' {
+                    ScanMode::DoubleQuote
+                } else if char == '\\' && posix {
+                    ScanMode::Escape
+                } else if char == '\'' && posix {
+                    ScanMode::SingleQuote
+                } else {
+                    self
+                }
+            }
+            ScanMode::SingleQuote => {
+                if char == '\'' {
+                    ScanMode::Normal
+                } else {
+                    self
+                } // no escape in single quotes
+            }
         }
-        return input;
-    };
-    let mut result = String::with_capacity(input.len() + n);
+    }
+}
 
-    for c in input.chars() {
-        if c.is_ascii() && memchr(c as u8, break_chars).is_some() {
-            result.push(esc_char);
+/// try to find an unclosed single/double quote in `s`.
+/// Return `None` if no unclosed quote is found.
+/// Return the unclosed quote position and if it is a double quote.
+fn find_unclosed_quote(s: &str) -> Option<(usize, Quote)> {
+    let rules = ShellRules::default();
+    let mut mode = ScanMode::Normal;
+    let mut quote_index = 0;
+    for (index, char) in s.char_indices() {
+        let next = mode.next(char, rules);
+        if mode == ScanMode::Normal
+            && (next == ScanMode::DoubleQuote || next == ScanMode::SingleQuote)
+        {
+            quote_index = index;
         }
-        result.push(c);
+        mode = next;
     }
-    result
+    if ScanMode::DoubleQuote == mode || ScanMode::EscapeInDoubleQuote == mode {
+        return Some((quote_index, Quote::Double));
+    } else if ScanMode::SingleQuote == mode {
+        return Some((quote_index, Quote::Single));
+    }
+    None
 }
 
-fn filename_complete(
-    path: &str,
-    esc_char: Option<char>,
-    break_chars: &[u8],
-    quote: Quote,
-) -> Vec<Pair> {
-    #[cfg(feature = "with-dirs")]
-    use dirs_next::home_dir;
-    use std::env::current_dir;
+/// Quoting and escaping rules used to split a line into words.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ShellRules {
+    /// POSIX shell: single quotes, double quotes and backslash escapes.
+    Posix,
+    /// Windows: only double quotes, backslash is a path separator (except
+    /// inside double quotes).
+    Windows,
+}
 
-    let sep = path::MAIN_SEPARATOR;
-    let (dir_name, file_name) = match path.rfind(sep) {
-        Some(idx) => path.split_at(idx + sep.len_utf8()),
-        None => ("", path),
-    };
+impl Default for ShellRules {
+    /// Rules of the current platform
+    #[cfg(windows)]
+    fn default() -> Self {
+        ShellRules::Windows
+    }
 
-    let dir_path = Path::new(dir_name);
-    let dir = if dir_path.starts_with("~") {
-        // ~[/...]
-        #[cfg(feature = "with-dirs")]
-        {
-            if let Some(home) = home_dir() {
-                match dir_path.strip_prefix("~") {
-                    Ok(rel_path) => home.join(rel_path),
-                    _ => home,
-                }
-            } else {
-                dir_path.to_path_buf()
+    /// Rules of the current platform
+    #[cfg(not(windows))]
+    fn default() -> Self {
+        ShellRules::Posix
+    }
+}
+
+/// Shell word (see [`tokenize`])
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShellWord {
+    /// Byte range of the raw word in the line, quotes and escape chars
+    /// included
+    pub range: Range<usize>,
+    /// Word content without quotes and escape chars
+    pub value: String,
+    /// Position and kind of a quote not closed before the end of the line
+    /// (only for the last word)
+    pub unclosed_quote: Option<(usize, Quote)>,
+}
+
+/// Words of a line (see [`tokenize`])
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Tokens {
+    /// Words in line order
+    pub words: Vec<ShellWord>,
+    /// Index in `words` of the word under (or just before) the cursor.
+    /// `None` when the cursor is surrounded by whitespaces.
+    pub cursor: Option<usize>,
+}
+
+impl Tokens {
+    /// Word under the cursor
+    #[must_use]
+    pub fn cursor_word(&self) -> Option<&ShellWord> {
+        self.cursor.map(|index| &self.words[index])
+    }
+}
+
+/// Split `line` into shell words according to `rules`: whitespaces inside
+/// quotes or escaped do not break words.
+///
+/// ```
+/// use rustyline::completion::{tokenize, ShellRules};
+///
+/// let tokens = tokenize("ls \"a b\" c", 8, ShellRules::Posix);
+/// assert_eq!(3, tokens.words.len());
+/// assert_eq!(3..8, tokens.words[1].range);
+/// assert_eq!("a b", tokens.words[1].value);
+/// assert_eq!(Some(1), tokens.cursor);
+/// ```
+#[must_use]
+pub fn tokenize(line: &str, pos: usize, rules: ShellRules) -> Tokens {
+    let mut words = Vec::new();
+    let mut mode = ScanMode::Normal;
+    let mut start = None;
+    let mut quote_index = 0;
+    for (index, char) in line.char_indices() {
+        if mode == ScanMode::Normal && char.is_whitespace() {
+            if let Some(start) = start.take() {
+                words.push(ShellWord {
+                    range: start..index,
+                    value: unquote_with(&line[start..index], rules),
+                    unclosed_quote: None,
+                });
             }
+            continue;
+        } else if start.is_none() {
+            start = Some(index);
         }
-        #[cfg(not(feature = "with-dirs"))]
+        let next = mode.next(char, rules);
+        if mode == ScanMode::Normal
+            && (next == ScanMode::DoubleQuote || next == ScanMode::SingleQuote)
         {
-            dir_path.to_path_buf()
+            quote_index = index;
         }
-    } else if dir_path.is_relative() {
-        // TODO ~user[/...] (https://crates.io/crates/users)
-        if let Ok(cwd) = current_dir() {
-            cwd.join(dir_path)
-        } else {
-            dir_path.to_path_buf()
+        mode = next;
+    }
+    if let Some(start) = start {
+        let unclosed_quote = match mode {
+            ScanMode::DoubleQuote | ScanMode::EscapeInDoubleQuote => {
+                Some((quote_index, Quote::Double))
+            }
+            ScanMode::SingleQuote => Some((quote_index, Quote::Single)),
+            _ => None,
+        };
+        words.push(ShellWord {
+            range: start..line.len(),
+            value: unquote_with(&line[start..], rules),
+            unclosed_quote,
+        });
+    }
+    let cursor = words
+        .iter()
+        .position(|word| word.range.start <= pos && pos <= word.range.end);
+    Tokens { words, cursor }
+}
+
+/// Split `line` into shell words: whitespaces inside quotes or escaped do not
+/// break words. Quotes and escape chars are kept as is.
+pub(crate) fn split_words(line: &str) -> Vec<&str> {
+    tokenize(line, line.len(), ShellRules::default())
+        .words
+        .into_iter()
+        .map(|word| &line[word.range])
+        .collect()
+}
+
+/// Kind of value expected by a positional argument or a flag of a
+/// [`CommandSpec`].
+pub enum ArgKind {
+    /// Any value, not completed
+    Any,
+    /// File or directory name
+    File,
+    /// Directory name
+    Directory,
+    /// One of the specified values
+    Choices(Vec<String>),
+    /// Values returned by the callback, given the (unescaped) prefix to
+    /// complete. The callback is responsible for filtering them.
+    Custom(Box<ArgCompleter>),
+}
+
+/// Callback of [`ArgKind::Custom`]
+pub type ArgCompleter = dyn Fn(&str) -> Vec<String> + Send + Sync;
+
+/// Flag of a [`CommandSpec`]: `-s`, `--long`, `--long value` or `--long=value`.
+pub struct Flag {
+    short: Option<char>,
+    long: Option<String>,
+    value: Option<ArgKind>,
+    description: Option<String>,
+}
+
+impl Flag {
+    /// Flag with only a short name: `-s`
+    #[must_use]
+    pub fn short(short: char) -> Self {
+        Self {
+            short: Some(short),
+            long: None,
+            value: None,
+            description: None,
         }
-    } else {
-        dir_path.to_path_buf()
-    };
+    }
 
-    let mut entries: Vec<Pair> = Vec::new();
+    /// Flag with a long name: `--long`
+    #[must_use]
+    pub fn long(long: &str) -> Self {
+        Self {
+            short: None,
+            long: Some(long.to_owned()),
+            value: None,
+            description: None,
+        }
+    }
 
-    // if dir doesn't exist, then don't offer any completions
-    if !dir.exists() {
-        return entries;
+    /// Short name alias
+    #[must_use]
+    pub fn with_short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
     }
 
-    // if any of the below IO operations have errors, just ignore them
-    if let Ok(read_dir) = dir.read_dir() {
-        let file_name = normalize(file_name);
-        for entry in read_dir.flatten() {
-            if let Some(s) = entry.file_name().to_str() {
-                let ns = normalize(s);
-                if ns.starts_with(file_name.as_ref()) {
-                    if let Ok(metadata) = fs::metadata(entry.path()) {
-                        let mut path = String::from(dir_name) + s;
-                        if metadata.is_dir() {
-                            path.push(sep);
-                        }
-                        entries.push(Pair {
-                            display: String::from(s),
-                            replacement: escape(path, esc_char, break_chars, quote),
-                        });
-                    } // else ignore PermissionDenied
+    /// Kind of the value expected after the flag
+    #[must_use]
+    pub fn with_value(mut self, kind: ArgKind) -> Self {
+        self.value = Some(kind);
+        self
+    }
+
+    /// Description displayed when listing alternatives
+    #[must_use]
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_owned());
+        self
+    }
+
+    fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.long
+            .iter()
+            .map(|long| format!("--{}", long))
+            .chain(self.short.iter().map(|short| format!("-{}", short)))
+    }
+}
+
+/// Node of the tree of commands completed by a [`CommandCompleter`]: a name,
+/// subcommands, flags and positional arguments.
+pub struct CommandSpec {
+    name: String,
+    description: Option<String>,
+    subcommands: Vec<CommandSpec>,
+    flags: Vec<Flag>,
+    args: Vec<ArgKind>,
+    variadic: bool, // the last argument can be repeated
+}
+
+impl CommandSpec {
+    /// Constructor
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            description: None,
+            subcommands: Vec::new(),
+            flags: Vec::new(),
+            args: Vec::new(),
+            variadic: false,
+        }
+    }
+
+    /// Description displayed when listing alternatives
+    #[must_use]
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_owned());
+        self
+    }
+
+    /// Add a subcommand, which can only be the first argument
+    #[must_use]
+    pub fn with_subcommand(mut self, subcommand: CommandSpec) -> Self {
+        self.subcommands.push(subcommand);
+        self
+    }
+
+    /// Add a flag
+    #[must_use]
+    pub fn with_flag(mut self, flag: Flag) -> Self {
+        self.flags.push(flag);
+        self
+    }
+
+    /// Add a positional argument
+    #[must_use]
+    pub fn with_arg(mut self, kind: ArgKind) -> Self {
+        self.args.push(kind);
+        self.variadic = false;
+        self
+    }
+
+    /// Add a positional argument which can be repeated
+    #[must_use]
+    pub fn with_args(mut self, kind: ArgKind) -> Self {
+        self.args.push(kind);
+        self.variadic = true;
+        self
+    }
+
+    fn flag(&self, name: &str) -> Option<&Flag> {
+        if let Some(long) = name.strip_prefix("--") {
+            self.flags.iter().find(|f| f.long.as_deref() == Some(long))
+        } else {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some('-'), Some(short), None) => {
+                    self.flags.iter().find(|f| f.short == Some(short))
                 }
+                _ => None,
             }
         }
     }
-    entries
+
+    fn arg(&self, index: usize) -> Option<&ArgKind> {
+        match self.args.get(index) {
+            None if self.variadic => self.args.last(),
+            arg => arg,
+        }
+    }
 }
 
-#[cfg(any(windows, target_os = "macos"))]
-fn normalize(s: &str) -> Cow<str> {
-    // case insensitive
-    Owned(s.to_lowercase())
+impl CommandTree for CommandSpec {
+    type Value = ArgKind;
+
+    fn subcommand(&self, name: &str) -> Option<&Self> {
+        self.subcommands.iter().find(|c| c.name == name)
+    }
+
+    fn flag_value(&self, name: &str) -> Option<&ArgKind> {
+        self.flag(name).and_then(|f| f.value.as_ref())
+    }
 }
 
-#[cfg(not(any(windows, target_os = "macos")))]
-fn normalize(s: &str) -> Cow<str> {
-    Cow::Borrowed(s)
+/// Tree of commands walked by [`walk_words`]
+pub(crate) trait CommandTree {
+    /// Value expected by a flag
+    type Value;
+    /// Subcommand named `name`
+    fn subcommand(&self, name: &str) -> Option<&Self>;
+    /// Value expected by the flag named `name` (`--long` or `-s`), if any
+    fn flag_value(&self, name: &str) -> Option<&Self::Value>;
 }
 
-/// Given a `line` and a cursor `pos`ition,
-/// try to find backward the start of a word.
-/// Return (0, `line[..pos]`) if no break char has been found.
-/// Return the word and its start position (idx, `line[idx..pos]`) otherwise.
-#[must_use]
-pub fn extract_word<'l>(
-    line: &'l str,
+/// Where the cursor is in a tree of commands (see [`walk_words`])
+pub(crate) struct WordWalk<'c, T: CommandTree> {
+    /// Innermost (sub)command
+    pub command: &'c T,
+    /// Number of positional arguments before the cursor
+    pub positional: usize,
+    /// Value expected by the flag preceding the cursor
+    pub flag_value: Option<&'c T::Value>,
+    /// A `--` precedes the cursor: only positional arguments follow
+    pub only_args: bool,
+    /// Word being completed, if the cursor is not after a space
+    pub current: Option<ShellWord>,
+}
+
+/// Walk the words of `line` before `pos` through the subcommands of `root`,
+/// skipping flags and their values.
+pub(crate) fn walk_words<'c, T: CommandTree>(
+    root: &'c T,
+    line: &str,
     pos: usize,
-    esc_char: Option<char>,
-    break_chars: &[u8],
-) -> (usize, &'l str) {
-    let line = &line[..pos];
-    if line.is_empty() {
-        return (0, line);
+) -> WordWalk<'c, T> {
+    let mut words = tokenize(&line[..pos], pos, ShellRules::default()).words;
+    let current = match words.last() {
+        Some(w) if w.range.end == pos => words.pop(),
+        _ => None,
+    };
+
+    let mut walk = WordWalk {
+        command: root,
+        positional: 0,
+        flag_value: None,
+        only_args: false,
+        current,
+    };
+    for word in words.iter().map(|w| &w.value) {
+        if walk.flag_value.take().is_some() {
+            continue;
+        }
+        if !walk.only_args && word == "--" {
+            walk.only_args = true;
+        } else if !walk.only_args && word.starts_with('-') && word.len() > 1 {
+            if word.contains('=') {
+                continue; // --long=value
+            }
+            walk.flag_value = if word.starts_with("--") {
+                walk.command.flag_value(word)
+            } else {
+                // last of grouped short flags: -xvf file
+                word.chars()
+                    .last()
+                    .and_then(|c| walk.command.flag_value(&format!("-{}", c)))
+            };
+        } else if let Some(subcommand) = walk
+            .command
+            .subcommand(word)
+            .filter(|_| walk.positional == 0)
+        {
+            walk.command = subcommand;
+        } else {
+            walk.positional += 1;
+        }
     }
-    let mut start = None;
-    for (i, c) in line.char_indices().rev() {
-        if let (Some(esc_char), true) = (esc_char, start.is_some()) {
-            if esc_char == c {
-                // escaped break char
-                start = None;
-                continue;
+    walk
+}
+
+/// A `Completer` for a tree of commands declared with [`CommandSpec`]: it
+/// completes command names, subcommand names, flags and the values of flags
+/// and positional arguments depending on their [`ArgKind`].
+///
+/// ```
+/// use rustyline::completion::{ArgKind, CommandSpec, CommandCompleter, Flag};
+///
+/// let completer = CommandCompleter::new()
+///     .with_command(
+///         CommandSpec::new("cd")
+///             .with_description("Change directory")
+///             .with_arg(ArgKind::Directory),
+///     )
+///     .with_command(
+///         CommandSpec::new("cargo")
+///             .with_flag(Flag::long("verbose").with_short('v'))
+///             .with_subcommand(
+///                 CommandSpec::new("build")
+///                     .with_flag(Flag::long("manifest-path").with_value(ArgKind::File)),
+///             ),
+///     );
+/// ```
+pub struct CommandCompleter {
+    root: CommandSpec,
+    files: FilenameCompleter,
+    directories: FilenameCompleter,
+}
+
+impl CommandCompleter {
+    /// Constructor
+    #[must_use]
+    pub fn new() -> Self {
+        Self::from_root(CommandSpec::new(""))
+    }
+
+    /// Completer for the arguments of `root`: its subcommands are the
+    /// top-level commands.
+    pub(crate) fn from_root(root: CommandSpec) -> Self {
+        Self {
+            root,
+            files: FilenameCompleter::new(),
+            directories: FilenameCompleter::builder()
+                .file_type(FileType::Directory)
+                .build(),
+        }
+    }
+
+    /// Add a top-level command
+    #[must_use]
+    pub fn with_command(mut self, command: CommandSpec) -> Self {
+        self.root.subcommands.push(command);
+        self
+    }
+
+    fn complete_arg(
+        &self,
+        kind: &ArgKind,
+        line: &str,
+        pos: usize,
+    ) -> Result<(usize, Vec<DescribedPair>)> {
+        let (start, prefix, quote) = current_word(line, pos);
+        let values = match kind {
+            ArgKind::Any => Vec::new(),
+            ArgKind::File | ArgKind::Directory => {
+                let completer = if let ArgKind::File = kind {
+                    &self.files
+                } else {
+                    &self.directories
+                };
+                let (start, pairs) = completer.complete_path(line, pos)?;
+                let candidates = pairs
+                    .into_iter()
+                    .map(|p| DescribedPair {
+                        display: p.display,
+                        replacement: p.replacement,
+                        description: None,
+                    })
+                    .collect();
+                return Ok((start, candidates));
             }
-            break;
+            ArgKind::Choices(choices) => choices
+                .iter()
+                .filter(|c| c.starts_with(prefix.as_ref()))
+                .map(|c| (c.clone(), None))
+                .collect(),
+            ArgKind::Custom(f) => f(&prefix).into_iter().map(|c| (c, None)).collect(),
+        };
+        Ok((start, described_pairs(values, quote)))
+    }
+}
+
+impl Default for CommandCompleter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Completer for CommandCompleter {
+    type Candidate = DescribedPair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<DescribedPair>)> {
+        let walk = walk_words(&self.root, line, pos);
+        let (command, positional) = (walk.command, walk.positional);
+        if let Some(kind) = walk.flag_value {
+            return self.complete_arg(kind, line, pos);
         }
-        if c.is_ascii() && memchr(c as u8, break_chars).is_some() {
-            start = Some(i + c.len_utf8());
-            if esc_char.is_none() {
-                break;
-            } // else maybe escaped...
+        let current = walk.current.map(|w| w.value).unwrap_or_default();
+        if !walk.only_args && current.starts_with('-') {
+            if let Some((name, _)) = current.split_once('=') {
+                return match command.flag_value(name) {
+                    Some(kind) => self.complete_arg(kind, line, pos),
+                    None => Ok((pos, Vec::new())),
+                };
+            }
+            let (start, _, quote) = current_word(line, pos);
+            let flags = command
+                .flags
+                .iter()
+                .flat_map(|f| f.names().map(move |name| (name, f.description.clone())))
+                .filter(|(name, _)| name.starts_with(current.as_str()))
+                .collect();
+            return Ok((start, described_pairs(flags, quote)));
+        }
+
+        let (start, mut candidates) = match command.arg(positional) {
+            Some(kind) => self.complete_arg(kind, line, pos)?,
+            None => (current_word(line, pos).0, Vec::new()),
+        };
+        if positional == 0 {
+            let (start, prefix, quote) = current_word(line, pos);
+            let subcommands = command
+                .subcommands
+                .iter()
+                .filter(|c| c.name.starts_with(prefix.as_ref()))
+                .map(|c| (c.name.clone(), c.description.clone()))
+                .collect();
+            let mut subcommands = described_pairs(subcommands, quote);
+            subcommands.append(&mut candidates);
+            return Ok((start, subcommands));
+        }
+        Ok((start, candidates))
+    }
+}
+
+/// A `Completer` which runs an external program to get the candidates, like
+/// bash `complete -C`.
+///
+/// The program is called with the name of the command, the word being
+/// completed and the word preceding it as arguments, and with the
+/// `COMP_LINE` (the whole line) and `COMP_POINT` (the cursor position, in
+/// characters) environment variables. It prints one candidate per line on
+/// its standard output.
+///
+/// If the program doesn't exit within the timeout, it is killed and no
+/// candidate is offered. On unix, the program runs in its own process group
+/// which is killed as a whole. Elsewhere, only the program is killed: its
+/// children keep running until they exit.
+///
+/// ```
+/// use rustyline::completion::ExternalCompleter;
+/// use std::time::Duration;
+///
+/// let completer = ExternalCompleter::new("my-completion-script")
+///     .with_arg("--bash")
+///     .with_timeout(Duration::from_millis(200));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExternalCompleter {
+    program: OsString,
+    args: Vec<OsString>,
+    timeout: Duration,
+}
+
+impl ExternalCompleter {
+    /// Completer running `program`
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        Self {
+            program: program.as_ref().to_owned(),
+            args: Vec::new(),
+            timeout: Duration::from_secs(1),
         }
     }
 
-    match start {
-        Some(start) => (start, &line[start..]),
-        None => (0, line),
+    /// Add an argument passed to the program before the ones of the
+    /// protocol
+    #[must_use]
+    pub fn with_arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
     }
-}
 
-/// Returns the longest common prefix among all `Candidate::replacement()`s.
-pub fn longest_common_prefix<C: Candidate>(candidates: &[C]) -> Option<&str> {
-    if candidates.is_empty() {
-        return None;
-    } else if candidates.len() == 1 {
-        return Some(candidates[0].replacement());
+    /// Maximum duration of the program.
+    ///
+    /// By default, 1 second.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
     }
-    let mut longest_common_prefix = 0;
-    'o: loop {
-        for (i, c1) in candidates.iter().enumerate().take(candidates.len() - 1) {
-            let b1 = c1.replacement().as_bytes();
-            let b2 = candidates[i + 1].replacement().as_bytes();
-            if b1.len() <= longest_common_prefix
-                || b2.len() <= longest_common_prefix
-                || b1[longest_common_prefix] != b2[longest_common_prefix]
-            {
-                break 'o;
+
+    /// Runs the program and returns its output, or `None` after the timeout.
+    fn run(&self, line: &str, pos: usize, words: [&str; 3]) -> Result<Option<String>> {
+        let mut command = process::Command::new(&self.program);
+        command
+            .args(&self.args)
+            .args(words)
+            .env("COMP_LINE", line)
+            .env("COMP_POINT", line[..pos].chars().count().to_string())
+            .env("COMP_TYPE", "9") // Tab
+            .env("COMP_KEY", "9")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        #[cfg(unix)]
+        {
+            // so that its children can be killed with it
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        let mut child = command.spawn()?;
+        let mut stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = String::new();
+            let result = stdout.read_to_string(&mut output).map(|_| output);
+            let _ = sender.send(result);
+        });
+        match receiver.recv_timeout(self.timeout) {
+            Ok(output) => {
+                child.wait()?;
+                Ok(Some(output?))
+            }
+            Err(_) => {
+                // the children may keep the standard output (and the reader
+                // thread) alive
+                #[cfg(unix)]
+                {
+                    use nix::sys::signal::{killpg, Signal};
+                    use nix::unistd::Pid;
+                    let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+                Ok(None)
             }
         }
-        longest_common_prefix += 1;
     }
-    let candidate = candidates[0].replacement();
-    while !candidate.is_char_boundary(longest_common_prefix) {
-        longest_common_prefix -= 1;
+}
+
+impl Completer for ExternalCompleter {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
+        let (start, word, quote) = current_word(line, pos);
+        // without the opening quote
+        let before = if quote == Quote::None {
+            &line[..start]
+        } else {
+            &line[..start - 1]
+        };
+        let words: Vec<String> = split_words(before).into_iter().map(unquote).collect();
+        let command = words.first().map_or(word.as_ref(), String::as_str);
+        let previous = words.last().map_or("", String::as_str);
+        let output = match self.run(line, pos, [command, &word, previous])? {
+            Some(output) => output,
+            None => return Ok((start, Vec::new())),
+        };
+        let (esc_char, break_chars): (_, &[u8]) = if quote == Quote::Double {
+            (DOUBLE_QUOTES_ESCAPE_CHAR, &DOUBLE_QUOTES_SPECIAL_CHARS)
+        } else {
+            (ESCAPE_CHAR, &DEFAULT_BREAK_CHARS)
+        };
+        let candidates = output
+            .lines()
+            .filter(|candidate| !candidate.is_empty())
+            .map(|candidate| Pair {
+                display: candidate.to_owned(),
+                replacement: escape(candidate.to_owned(), esc_char, break_chars, quote),
+            })
+            .collect();
+        Ok((start, candidates))
     }
-    if longest_common_prefix == 0 {
-        return None;
+}
+
+/// Start, unescaped content and quote of the word being completed.
+fn current_word(line: &str, pos: usize) -> (usize, Cow<'_, str>, Quote) {
+    if let Some((idx, quote)) = find_unclosed_quote(&line[..pos]) {
+        let start = idx + 1;
+        let esc_char = if quote == Quote::Double {
+            DOUBLE_QUOTES_ESCAPE_CHAR
+        } else {
+            None
+        };
+        (start, unescape(&line[start..pos], esc_char), quote)
+    } else {
+        let (start, word) = extract_word(line, pos, ESCAPE_CHAR, &DEFAULT_BREAK_CHARS);
+        (start, unescape(word, ESCAPE_CHAR), Quote::None)
     }
-    Some(&candidate[0..longest_common_prefix])
 }
 
-#[derive(Eq, PartialEq)]
-enum ScanMode {
-    DoubleQuote,
-    Escape,
-    EscapeInDoubleQuote,
-    Normal,
-    SingleQuote,
+/// Escapes `values` depending on `quote`.
+fn described_pairs(values: Vec<(String, Option<String>)>, quote: Quote) -> Vec<DescribedPair> {
+    let (esc_char, break_chars): (_, &[u8]) = if quote == Quote::Double {
+        (DOUBLE_QUOTES_ESCAPE_CHAR, &DOUBLE_QUOTES_SPECIAL_CHARS)
+    } else {
+        (ESCAPE_CHAR, &DEFAULT_BREAK_CHARS)
+    };
+    values
+        .into_iter()
+        .map(|(value, description)| DescribedPair {
+            display: value.clone(),
+            replacement: escape(value, esc_char, break_chars, quote),
+            description,
+        })
+        .collect()
 }
 
-/// try to find an unclosed single/double quote in `s`.
-/// Return `None` if no unclosed quote is found.
-/// Return the unclosed quote position and if it is a double quote.
-fn find_unclosed_quote(s: &str) -> Option<(usize, Quote)> {
-    let char_indices = s.char_indices();
+/// Remove quotes and escape chars from a shell `word`.
+pub(crate) fn unquote(word: &str) -> String {
+    unquote_with(word, ShellRules::default())
+}
+
+/// Remove quotes and escape chars from a `word` according to `rules`.
+fn unquote_with(word: &str, rules: ShellRules) -> String {
+    let mut result = String::with_capacity(word.len());
     let mut mode = ScanMode::Normal;
-    let mut quote_index = 0;
-    for (index, char) in char_indices {
-        match mode {
-            ScanMode::DoubleQuote => {
-                if char == '"' {
-                    mode = ScanMode::Normal;
-                } else if char == '\\' {
-                    // both windows and unix support escape in double quote
-                    mode = ScanMode::EscapeInDoubleQuote;
-                }
-            }
-            ScanMode::Escape => {
-                mode = ScanMode::Normal;
-            }
-            ScanMode::EscapeInDoubleQuote => {
-                mode = ScanMode::DoubleQuote;
-            }
-            ScanMode::Normal => {
-                if char == '"' {
-                    mode = ScanMode::DoubleQuote;
-                    quote_index = index;
-                } else if char == '\\' && cfg!(not(windows)) {
-                    mode = ScanMode::Escape;
-                } else if char == '\'' && cfg!(not(windows)) {
-                    mode = ScanMode::SingleQuote;
-                    quote_index = index;
-                }
-            }
-            ScanMode::SingleQuote => {
-                if char == '\'' {
-                    mode = ScanMode::Normal;
-                } // no escape in single quotes
-            }
-        };
+    for char in word.chars() {
+        let next = mode.next(char, rules);
+        // inside double quotes, a backslash only escapes these chars
+        if mode == ScanMode::EscapeInDoubleQuote && !matches!(char, '$' | '`' | '"' | '\\' | '\n') {
+            result.push('\\');
+        }
+        let skip = matches!(
+            (mode, next),
+            (
+                ScanMode::Normal,
+                ScanMode::DoubleQuote | ScanMode::SingleQuote | ScanMode::Escape
+            ) | (
+                ScanMode::DoubleQuote,
+                ScanMode::Normal | ScanMode::EscapeInDoubleQuote
+            ) | (ScanMode::SingleQuote, ScanMode::Normal)
+        );
+        if !skip {
+            result.push(char);
+        }
+        mode = next;
     }
-    if ScanMode::DoubleQuote == mode || ScanMode::EscapeInDoubleQuote == mode {
-        return Some((quote_index, Quote::Double));
-    } else if ScanMode::SingleQuote == mode {
-        return Some((quote_index, Quote::Single));
+    if mode == ScanMode::EscapeInDoubleQuote {
+        result.push('\\');
     }
-    None
+    result
 }
 
 #[cfg(test)]
@@ -580,6 +2977,285 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn expand_dir_name() {
+        use super::Quote;
+        use std::path::PathBuf;
+        let expand = |dir_name, quote| {
+            super::expand_dir_name_with(
+                dir_name,
+                quote,
+                |user| match user {
+                    "" => Some(PathBuf::from("/home/me")),
+                    "bob" => Some(PathBuf::from("/home/bob")),
+                    _ => None,
+                },
+                |name| Some("/tmp".to_owned()).filter(|_| name == "RUSTYLINE_EXPAND_DIR"),
+            )
+        };
+        assert_eq!("/tmp/a/", expand("$RUSTYLINE_EXPAND_DIR/a/", Quote::None));
+        assert_eq!(
+            "/tmp/a/",
+            expand("${RUSTYLINE_EXPAND_DIR}/a/", Quote::Double)
+        );
+        assert_eq!(
+            "$RUSTYLINE_EXPAND_DIR/",
+            expand("$RUSTYLINE_EXPAND_DIR/", Quote::Single)
+        );
+        assert_eq!(
+            "$RUSTYLINE_UNSET_DIR/",
+            expand("$RUSTYLINE_UNSET_DIR/", Quote::None)
+        );
+        assert_eq!("~/", expand("~/", Quote::Double));
+        assert_eq!("/home/me/a/", expand("~/a/", Quote::None));
+        assert_eq!("/home/me", expand("~", Quote::None));
+        assert_eq!("/home/bob/a/", expand("~bob/a/", Quote::None));
+        #[cfg(windows)]
+        assert_eq!("/home/me\\a\\", expand("~\\a\\", Quote::None));
+        assert_eq!(
+            "~rustyline-unknown-user/",
+            expand("~rustyline-unknown-user/", Quote::None)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    pub fn escape_dir_name() {
+        let break_chars: &[u8] = &super::DEFAULT_BREAK_CHARS;
+        assert_eq!(
+            "${HOME}/User\\ Information/",
+            super::escape_dir_name(
+                "${HOME}/User Information/",
+                Some('\\'),
+                break_chars,
+                super::Quote::None
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    pub fn complete_path_var() {
+        // set by cargo when running tests
+        if std::env::var_os("CARGO_MANIFEST_DIR").is_none() {
+            return;
+        }
+        let completer = super::FilenameCompleter::new();
+        for line in &["ls $CARGO_MANIFEST_DIR/sr", "ls ${CARGO_MANIFEST_DIR}/sr"] {
+            let (start, candidates) = completer.complete_path(line, line.len()).unwrap();
+            assert_eq!(3, start);
+            assert_eq!(1, candidates.len());
+            assert_eq!("src", candidates[0].display);
+            let prefix = &line[3..line.len() - 2];
+            assert_eq!(format!("{}src/", prefix), candidates[0].replacement);
+        }
+
+        std::env::set_var("RUSTYLINE_TEST_RC", "rc");
+        let line = "ls $CARGO_MANIFEST_DIR/s$RUSTYLINE_TEST_RC/complet";
+        let (start, candidates) = completer.complete_path(line, line.len()).unwrap();
+        assert_eq!(3, start);
+        assert_eq!(1, candidates.len());
+        assert_eq!("completion.rs", candidates[0].display);
+    }
+
+    #[test]
+    pub fn glob_match() {
+        assert!(super::glob_match("*.toml", "Cargo.toml"));
+        assert!(super::glob_match("*.toml", ".toml"));
+        assert!(!super::glob_match("*.toml", "Cargo.lock"));
+        assert!(super::glob_match("Cargo.*", "Cargo.lock"));
+        assert!(super::glob_match("?a*o*", "Cargo.toml"));
+        assert!(!super::glob_match("?", ""));
+        assert!(super::glob_match("*", ""));
+        assert!(super::glob_match("[ab]*", "b.rs"));
+        assert!(!super::glob_match("[!ab]*", "b.rs"));
+        assert!(super::glob_match("file[0-9].rs", "file7.rs"));
+        assert!(!super::glob_match("file[0-9].rs", "filex.rs"));
+        assert!(super::glob_match("[]]", "]"));
+        assert!(super::glob_match("a[b", "a[b"));
+    }
+
+    #[test]
+    pub fn glob_expand() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        std::fs::create_dir_all(dir.path().join("src").join("sub")).unwrap();
+        for name in &[
+            "a.rs",
+            "b.txt",
+            ".hidden.rs",
+            "src/lib.rs",
+            "src/sub/mod.rs",
+        ] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let sep = std::path::MAIN_SEPARATOR;
+        let expand = |pattern: &str| -> Vec<String> {
+            super::glob_expand(&format!("{}{}{}", root, sep, pattern))
+                .into_iter()
+                .map(|path| path[root.len() + 1..].replace(sep, "/"))
+                .collect()
+        };
+        assert_eq!(vec!["a.rs"], expand("*.rs"));
+        assert_eq!(vec![".hidden.rs"], expand(".*.rs"));
+        assert_eq!(vec!["a.rs", "b.txt"], expand("?.*"));
+        assert_eq!(
+            vec!["a.rs", "src/lib.rs", "src/sub/mod.rs"],
+            expand("**/*.rs")
+        );
+        assert_eq!(vec!["src/sub/mod.rs"], expand("src/*/*.rs"));
+        assert_eq!(vec!["src/"], expand("s*/"));
+        assert!(expand("*.py").is_empty());
+    }
+
+    #[test]
+    pub fn history_completer() {
+        use super::{Completer, HistoryCompleter};
+        let mut history = crate::history::History::new();
+        history.add("cargo build --release");
+        history.add("cat Cargo.toml");
+        history.add("cargo test");
+        let ctx = crate::Context::new(&history);
+        let (start, candidates) = HistoryCompleter.complete("git ca", 6, &ctx).unwrap();
+        assert_eq!(4, start);
+        assert_eq!(vec!["cargo", "cat"], candidates);
+        let (_, candidates) = HistoryCompleter.complete("cargo --", 8, &ctx).unwrap();
+        assert_eq!(vec!["--release"], candidates);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn external_completer() {
+        use super::{Completer, ExternalCompleter};
+        use std::time::Duration;
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+        let completer = ExternalCompleter::new("sh")
+            .with_arg("-c")
+            .with_arg(r#"printf '%s\n' "$1" "$2" "$3" "$COMP_LINE" "$COMP_POINT" """#)
+            .with_arg("sh");
+        let (start, candidates) = completer.complete("git co", 6, &ctx).unwrap();
+        assert_eq!(4, start);
+        let candidates: Vec<_> = candidates.into_iter().map(|c| c.replacement).collect();
+        assert_eq!(vec!["git", "co", "git", "git\\ co", "6"], candidates);
+        let (start, candidates) = completer.complete("ls \"a b", 7, &ctx).unwrap();
+        assert_eq!(4, start);
+        assert_eq!("a b", candidates[1].display);
+        assert_eq!("ls", candidates[2].display);
+        // in characters
+        let (_, candidates) = completer.complete("ls é", 5, &ctx).unwrap();
+        assert_eq!("4", candidates[4].display);
+
+        let completer = ExternalCompleter::new("sh")
+            .with_arg("-c")
+            .with_arg("echo never; sleep 10")
+            .with_timeout(Duration::from_millis(50));
+        let (_, candidates) = completer.complete("ls ", 3, &ctx).unwrap();
+        assert!(candidates.is_empty());
+        assert!(ExternalCompleter::new("/nonexistent/program")
+            .complete("ls ", 3, &ctx)
+            .is_err());
+    }
+
+    #[test]
+    pub fn fuzzy_completer() {
+        use super::{Completer, FuzzyCompleter};
+        use crate::config::CompletionType;
+        use crate::highlight::Highlighter;
+
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+        let completer = FuzzyCompleter::new(WordCompleter(3, &["foobar", "fb", "bar", "f_b"]));
+        let (start, candidates) = completer.complete("ls fb", 5, &ctx).unwrap();
+        assert_eq!(3, start);
+        assert_eq!(vec!["fb", "f_b", "foobar"], candidates);
+        assert_eq!(
+            "\x1b[1mf\x1b[22m_\x1b[1mb\x1b[22m",
+            completer.highlight_candidate("f_b", CompletionType::List)
+        );
+        assert_eq!(
+            "bar",
+            completer.highlight_candidate("bar", CompletionType::List)
+        );
+    }
+
+    #[test]
+    pub fn filename_completer_builder() {
+        use super::{FileType, FilenameCompleter};
+        use crate::config::CompletionType;
+        use crate::highlight::Highlighter;
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        for name in &["Cargo.toml", "Cargo.lock", "README.md", ".gitignore"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let prefix = format!(
+            "{}{}",
+            dir.path().to_str().unwrap(),
+            std::path::MAIN_SEPARATOR
+        );
+        let complete = |completer: &FilenameCompleter, file_name: &str| -> Vec<String> {
+            let line = format!("{}{}", prefix, file_name);
+            let (_, candidates) = completer.complete_path(&line, line.len()).unwrap();
+            candidates.into_iter().map(|c| c.display).collect()
+        };
+
+        let completer = FilenameCompleter::new();
+        assert_eq!(
+            vec![
+                ".git",
+                ".gitignore",
+                "Cargo.lock",
+                "Cargo.toml",
+                "README.md",
+                "src"
+            ],
+            complete(&completer, "")
+        );
+        let completer = FilenameCompleter::builder()
+            .file_type(FileType::Directory)
+            .hide_dotfiles(true)
+            .build();
+        assert_eq!(vec!["src"], complete(&completer, ""));
+        assert_eq!(vec![".git"], complete(&completer, "."));
+        let completer = FilenameCompleter::builder().extension(".toml").build();
+        assert_eq!(vec![".git", "Cargo.toml", "src"], complete(&completer, ""));
+        let completer = FilenameCompleter::builder()
+            .glob("*.md")
+            .glob("*.lock")
+            .case_insensitive(true)
+            .build();
+        assert_eq!(vec!["Cargo.lock"], complete(&completer, "c"));
+        assert_eq!(vec!["README.md"], complete(&completer, "r"));
+        let completer = FilenameCompleter::builder()
+            .case_insensitive(true)
+            .map_case(true)
+            .build();
+        assert_eq!(vec!["README.md"], complete(&completer, "read"));
+        assert!(complete(&completer, "cargo-").is_empty());
+        std::fs::write(dir.path().join("my_file"), "").unwrap();
+        assert_eq!(vec!["my_file"], complete(&completer, "MY-"));
+        let completer = FilenameCompleter::builder()
+            .ls_colors(crate::highlight::LsColors::parse("di=01;34:*.md=33"))
+            .build();
+        completer.complete_path(&prefix, prefix.len()).unwrap();
+        assert_eq!(
+            Some(&super::FileKind::Directory),
+            completer.file_kinds.0.lock().unwrap().get("src")
+        );
+        let highlight = |candidate| completer.highlight_candidate(candidate, CompletionType::List);
+        assert_eq!("\x1b[01;34msrc\x1b[0m", highlight("src"));
+        assert_eq!("\x1b[33mREADME.md\x1b[0m", highlight("README.md"));
+        assert_eq!("Cargo.toml", highlight("Cargo.toml"));
+        let completer = FilenameCompleter::builder().mark_directories(false).build();
+        let line = format!("{}s", prefix);
+        let (_, candidates) = completer.complete_path(&line, line.len()).unwrap();
+        assert_eq!(format!("{}src", prefix), candidates[0].replacement);
+        // file kinds are only needed with `ls_colors`
+        assert!(completer.file_kinds.0.lock().unwrap().is_empty());
+    }
+
     #[test]
     pub fn longest_common_prefix() {
         let mut candidates = vec![];
@@ -615,6 +3291,21 @@ mod tests {
         assert_eq!(Some("f"), lcp);
     }
 
+    #[test]
+    pub fn longest_common_prefix_ignore_case() {
+        use super::longest_common_prefix_ignore_case as lcp;
+        assert_eq!(None, lcp::<String>(&[], false));
+        let candidates = vec![String::from("readme"), String::from("README.md")];
+        assert_eq!(Some("readme"), lcp(&candidates, false));
+        let candidates = vec![String::from("FÉE"), String::from("fée"), String::from("Fe")];
+        assert_eq!(Some("F"), lcp(&candidates, false));
+        let candidates = vec![String::from("foo-bar"), String::from("Foo_Baz")];
+        assert_eq!(Some("foo"), lcp(&candidates, false));
+        assert_eq!(Some("foo-ba"), lcp(&candidates, true));
+        let candidates = vec![String::from("abc"), String::from("xyz")];
+        assert_eq!(None, lcp(&candidates, true));
+    }
+
     #[test]
     pub fn find_unclosed_quote() {
         assert_eq!(None, super::find_unclosed_quote("ls /etc"));
@@ -632,9 +3323,453 @@ mod tests {
         )
     }
 
+    struct WordCompleter(usize, &'static [&'static str]);
+
+    impl super::Completer for WordCompleter {
+        type Candidate = String;
+
+        fn complete(
+            &self,
+            _line: &str,
+            _pos: usize,
+            _ctx: &crate::Context<'_>,
+        ) -> crate::Result<(usize, Vec<String>)> {
+            Ok((self.0, self.1.iter().map(|s| (*s).to_owned()).collect()))
+        }
+    }
+
+    #[test]
+    pub fn fallback_completer() {
+        use super::{Completer, FallbackCandidate, FallbackCompleter};
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+
+        let completer = FallbackCompleter::new(WordCompleter(0, &[]), WordCompleter(3, &["ls"]));
+        assert_eq!(
+            (3, vec![FallbackCandidate::Second("ls".to_owned())]),
+            completer.complete("", 0, &ctx).unwrap()
+        );
+        let completer =
+            FallbackCompleter::new(WordCompleter(1, &["cd"]), WordCompleter(3, &["ls"]));
+        assert_eq!(
+            (1, vec![FallbackCandidate::First("cd".to_owned())]),
+            completer.complete("", 0, &ctx).unwrap()
+        );
+    }
+
+    /// Appends a space after the elected candidate
+    struct SpaceCompleter(WordCompleter);
+
+    impl super::Completer for SpaceCompleter {
+        type Candidate = String;
+
+        fn complete(
+            &self,
+            line: &str,
+            pos: usize,
+            ctx: &crate::Context<'_>,
+        ) -> crate::Result<(usize, Vec<String>)> {
+            self.0.complete(line, pos, ctx)
+        }
+
+        fn update(&self, line: &mut crate::line_buffer::LineBuffer, start: usize, elected: &str) {
+            let end = line.pos();
+            line.replace(start..end, &format!("{} ", elected));
+        }
+    }
+
+    #[test]
+    pub fn fallback_completer_update() {
+        use super::{Completer, FallbackCompleter};
+        use crate::line_buffer::LineBuffer;
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+
+        let completer = FallbackCompleter::new(
+            WordCompleter(0, &[]),
+            SpaceCompleter(WordCompleter(0, &["ls"])),
+        );
+        let (start, candidates) = completer.complete("l", 1, &ctx).unwrap();
+        let mut line = LineBuffer::init("l", 1, None);
+        completer.update_candidate(&mut line, start, &candidates[0], "ls");
+        assert_eq!("ls ", line.as_str());
+
+        let completer = FallbackCompleter::new(
+            WordCompleter(0, &["cd"]),
+            SpaceCompleter(WordCompleter(0, &["ls"])),
+        );
+        let (start, candidates) = completer.complete("c", 1, &ctx).unwrap();
+        let mut line = LineBuffer::init("c", 1, None);
+        completer.update_candidate(&mut line, start, &candidates[0], "cd");
+        assert_eq!("cd", line.as_str());
+    }
+
+    struct CountingCompleter(std::cell::Cell<usize>);
+
+    impl super::Completer for CountingCompleter {
+        type Candidate = String;
+
+        fn complete(
+            &self,
+            line: &str,
+            pos: usize,
+            _ctx: &crate::Context<'_>,
+        ) -> crate::Result<(usize, Vec<String>)> {
+            self.0.set(self.0.get() + 1);
+            let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+            let word = &line[start..pos];
+            let candidates = ["src/", "src/lib.rs", "src/line.rs", "target/"]
+                .iter()
+                .filter(|c| c.starts_with(word))
+                .map(|c| (*c).to_owned())
+                .collect();
+            Ok((start, candidates))
+        }
+    }
+
+    #[test]
+    pub fn caching_completer() {
+        use super::{CachingCompleter, Completer};
+        use std::time::Duration;
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+
+        let completer = CachingCompleter::new(CountingCompleter(Default::default()))
+            .with_matcher(|word, candidate: &String| candidate.starts_with(word));
+        let complete = |line: &str| completer.complete(line, line.len(), &ctx).unwrap();
+        assert_eq!(4, complete("ls ").1.len());
+        // filtered from the cache
+        assert_eq!(
+            (
+                3,
+                vec![
+                    "src/".to_owned(),
+                    "src/lib.rs".to_owned(),
+                    "src/line.rs".to_owned()
+                ]
+            ),
+            complete("ls s")
+        );
+        assert_eq!(
+            (3, vec!["src/lib.rs".to_owned(), "src/line.rs".to_owned()]),
+            complete("ls src/li")
+        );
+        assert_eq!(1, completer.completer.0.get());
+        // no longer candidate
+        assert_eq!(
+            (3, vec!["src/lib.rs".to_owned()]),
+            complete("ls src/lib.rs")
+        );
+        assert_eq!(2, completer.completer.0.get());
+        // other line prefix
+        complete("cd s");
+        assert_eq!(3, completer.completer.0.get());
+        completer.clear();
+        complete("cd s");
+        assert_eq!(4, completer.completer.0.get());
+
+        let completer =
+            CachingCompleter::new(CountingCompleter(Default::default())).with_ttl(Duration::ZERO);
+        completer.complete("ls ", 3, &ctx).unwrap();
+        completer.complete("ls ", 3, &ctx).unwrap();
+        assert_eq!(2, completer.completer.0.get());
+
+        // without matcher, only the same word is served from the cache
+        let completer = CachingCompleter::new(CountingCompleter(Default::default()));
+        completer.complete("ls ", 3, &ctx).unwrap();
+        completer.complete("ls s", 4, &ctx).unwrap();
+        assert_eq!(2, completer.completer.0.get());
+        assert_eq!(3, completer.complete("ls s", 4, &ctx).unwrap().1.len());
+        assert_eq!(2, completer.completer.0.get());
+    }
+
+    #[test]
+    pub fn merge_completer() {
+        use super::{Completer, MergeCandidate, MergeCompleter};
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+
+        let completer = MergeCompleter::<String>::new();
+        assert_eq!((0, vec![]), completer.complete("", 0, &ctx).unwrap());
+        let completer = MergeCompleter::new()
+            .with_completer(WordCompleter(0, &[]))
+            .with_completer(WordCompleter(1, &["cd", "ls"]))
+            .with_completer(WordCompleter(2, &["rm"]))
+            .with_completer(WordCompleter(1, &["ls", "mv"]));
+        assert_eq!(
+            (
+                1,
+                vec![
+                    MergeCandidate {
+                        candidate: "cd".to_owned(),
+                        completer: 1
+                    },
+                    MergeCandidate {
+                        candidate: "ls".to_owned(),
+                        completer: 1
+                    },
+                    MergeCandidate {
+                        candidate: "mv".to_owned(),
+                        completer: 3
+                    }
+                ]
+            ),
+            completer.complete("", 0, &ctx).unwrap()
+        );
+    }
+
+    /// Appends a space after the only candidate
+    struct SuffixCompleter;
+
+    impl super::Completer for SuffixCompleter {
+        type Candidate = String;
+
+        fn completion(
+            &self,
+            _line: &str,
+            _pos: usize,
+            _ctx: &crate::Context<'_>,
+        ) -> crate::Result<super::Completion<String>> {
+            Ok(super::Completion::new(0, vec!["ls".to_owned()]).with_suffix(' '))
+        }
+    }
+
+    #[test]
+    pub fn completion_options() {
+        use super::{
+            BackgroundCompleter, CachingCompleter, Candidate, Completer, FallbackCompleter,
+            FuzzyCompleter, MergeCompleter,
+        };
+        use std::time::Duration;
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+
+        let completion = FallbackCompleter::new(WordCompleter(0, &[]), SuffixCompleter)
+            .completion("l", 1, &ctx)
+            .unwrap();
+        assert_eq!(Some(' '), completion.suffix());
+        let completion = MergeCompleter::new()
+            .with_completer(WordCompleter(0, &[]))
+            .with_completer(SuffixCompleter)
+            .with_completer(WordCompleter(0, &["ls", "lsof"]))
+            .completion("l", 1, &ctx)
+            .unwrap();
+        assert_eq!(
+            vec!["ls", "lsof"],
+            completion
+                .candidates
+                .iter()
+                .map(Candidate::replacement)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(' '), completion.suffix());
+        let completion = FuzzyCompleter::new(SuffixCompleter)
+            .completion("l", 1, &ctx)
+            .unwrap();
+        assert_eq!(Some(' '), completion.suffix());
+        let completer = BackgroundCompleter::new(SuffixCompleter);
+        let completion = completer.completion("l", 1, &ctx).unwrap();
+        assert_eq!(Some(' '), completion.suffix());
+        let pending = completer.complete_in_background("l", 1, &ctx).unwrap();
+        let completion = pending.wait(Duration::from_secs(10)).unwrap().unwrap();
+        assert_eq!(Some(' '), completion.suffix());
+
+        let completer = CachingCompleter::new(completer);
+        assert!(completer.complete_in_background("l", 1, &ctx).is_some());
+        let completion = completer.completion("l", 1, &ctx).unwrap();
+        assert_eq!(Some(' '), completion.suffix());
+        // from the cache
+        assert!(completer.complete_in_background("l", 1, &ctx).is_none());
+        let completion = completer.completion("l", 1, &ctx).unwrap();
+        assert_eq!(Some(' '), completion.suffix());
+    }
+
+    #[test]
+    pub fn merge_completer_update() {
+        use super::{Completer, MergeCompleter};
+        use crate::line_buffer::LineBuffer;
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+
+        let completer = MergeCompleter::new()
+            .with_completer(WordCompleter(0, &["cd"]))
+            .with_completer(SpaceCompleter(WordCompleter(0, &["ls"])));
+        let (start, candidates) = completer.complete("", 0, &ctx).unwrap();
+        let mut line = LineBuffer::init("", 0, None);
+        completer.update_candidate(&mut line, start, &candidates[1], "ls");
+        assert_eq!("ls ", line.as_str());
+        let mut line = LineBuffer::init("", 0, None);
+        completer.update_candidate(&mut line, start, &candidates[0], "cd");
+        assert_eq!("cd", line.as_str());
+    }
+
+    #[test]
+    pub fn split_words() {
+        assert!(super::split_words("  ").is_empty());
+        assert_eq!(
+            vec!["ls", "-l", "/etc"],
+            super::split_words(" ls  -l /etc ")
+        );
+        assert_eq!(
+            vec!["ls", "\"User Information\"", "/etc"],
+            super::split_words("ls \"User Information\" /etc")
+        );
+        if cfg!(not(windows)) {
+            assert_eq!(
+                vec!["ls", "/User\\ Information", "'a b'"],
+                super::split_words("ls /User\\ Information 'a b'")
+            );
+        }
+    }
+
+    #[test]
+    pub fn tokenize() {
+        use super::{tokenize, Quote, ShellRules, ShellWord};
+        let tokens = tokenize("  ", 1, ShellRules::Posix);
+        assert!(tokens.words.is_empty());
+        assert_eq!(None, tokens.cursor);
+
+        let line = "cat 'a b' c\\ d \"e";
+        let tokens = tokenize(line, line.len(), ShellRules::Posix);
+        assert_eq!(
+            vec![
+                ShellWord {
+                    range: 0..3,
+                    value: "cat".to_owned(),
+                    unclosed_quote: None
+                },
+                ShellWord {
+                    range: 4..9,
+                    value: "a b".to_owned(),
+                    unclosed_quote: None
+                },
+                ShellWord {
+                    range: 10..14,
+                    value: "c d".to_owned(),
+                    unclosed_quote: None
+                },
+                ShellWord {
+                    range: 15..17,
+                    value: "e".to_owned(),
+                    unclosed_quote: Some((15, Quote::Double))
+                },
+            ],
+            tokens.words
+        );
+        assert_eq!(Some(3), tokens.cursor);
+        assert_eq!(
+            Some("a b"),
+            tokenize(line, 9, ShellRules::Posix)
+                .cursor_word()
+                .map(|w| w.value.as_str())
+        );
+        assert_eq!(None, tokenize("ls  -l", 3, ShellRules::Posix).cursor);
+
+        let line = "dir 'a b' c:\\\"Program Files\"";
+        let tokens = tokenize(line, 0, ShellRules::Windows);
+        let values: Vec<&str> = tokens.words.iter().map(|w| w.value.as_str()).collect();
+        assert_eq!(vec!["dir", "'a", "b'", "c:\\Program Files"], values);
+        assert_eq!(Some(0), tokens.cursor);
+        let tokens = tokenize("type \"a b", 7, ShellRules::Windows);
+        assert_eq!(Some((5, Quote::Double)), tokens.words[1].unclosed_quote);
+        assert_eq!(Some(1), tokens.cursor);
+    }
+
+    #[test]
+    pub fn unquote() {
+        assert_eq!("ls", super::unquote("ls"));
+        assert_eq!("a b", super::unquote("\"a b\""));
+        if cfg!(not(windows)) {
+            assert_eq!("a b", super::unquote("'a b'"));
+            assert_eq!("a b", super::unquote("a\\ b"));
+        }
+        let posix = |word| super::unquote_with(word, super::ShellRules::Posix);
+        assert_eq!("a\\b", posix("\"a\\b\""));
+        assert_eq!("a\"b", posix("\"a\\\"b\""));
+        assert_eq!("a\\b", posix("\"a\\\\b\""));
+        assert_eq!("a$b", posix("\"a\\$b\""));
+    }
+
+    #[test]
+    pub fn command_completer() {
+        use super::{ArgKind, CommandCompleter, CommandSpec, Completer, Flag};
+        let history = crate::history::History::new();
+        let ctx = crate::Context::new(&history);
+        let completer = CommandCompleter::new()
+            .with_command(CommandSpec::new("help"))
+            .with_command(
+                CommandSpec::new("git")
+                    .with_flag(Flag::long("version"))
+                    .with_flag(
+                        Flag::long("color")
+                            .with_short('c')
+                            .with_value(ArgKind::Choices(vec![
+                                "always".to_owned(),
+                                "never".to_owned(),
+                            ]))
+                            .with_description("When to use colors"),
+                    )
+                    .with_subcommand(CommandSpec::new("add").with_args(ArgKind::Any))
+                    .with_subcommand(
+                        CommandSpec::new("checkout")
+                            .with_description("Switch branches")
+                            .with_arg(ArgKind::Custom(Box::new(|prefix| {
+                                ["main", "my branch"]
+                                    .iter()
+                                    .filter(|b| b.starts_with(prefix))
+                                    .map(|b| (*b).to_owned())
+                                    .collect()
+                            }))),
+                    ),
+            );
+        let complete = |line: &str| {
+            let (start, candidates) = completer.complete(line, line.len(), &ctx).unwrap();
+            let candidates: Vec<_> = candidates
+                .into_iter()
+                .map(|c| (c.replacement, c.description))
+                .collect();
+            (start, candidates)
+        };
+        let pair = |r: &str| (r.to_owned(), None);
+
+        assert_eq!((0, vec![pair("git")]), complete("g"));
+        assert_eq!((4, vec![pair("add")]), complete("git a"));
+        assert_eq!(
+            (
+                4,
+                vec![("checkout".to_owned(), Some("Switch branches".to_owned()))]
+            ),
+            complete("git c")
+        );
+        assert_eq!(
+            (
+                4,
+                vec![
+                    pair("--version"),
+                    ("--color".to_owned(), Some("When to use colors".to_owned())),
+                    ("-c".to_owned(), Some("When to use colors".to_owned())),
+                ]
+            ),
+            complete("git -")
+        );
+        assert_eq!((12, vec![pair("always")]), complete("git --color a"));
+        assert_eq!((7, vec![pair("never")]), complete("git -c n"));
+        assert_eq!((12, vec![pair("never")]), complete("git --color=n"));
+        // flags and their values are skipped
+        assert_eq!((13, vec![pair("add")]), complete("git -c never a"));
+        assert_eq!((8, vec![]), complete("git add c"));
+        if cfg!(not(windows)) {
+            assert_eq!(
+                (13, vec![pair("main"), pair("my\\ branch")]),
+                complete("git checkout m")
+            );
+        }
+        assert_eq!((14, vec![pair("my branch")]), complete("git checkout \"my"));
+    }
+
     #[cfg(windows)]
     #[test]
     pub fn normalize() {
-        assert_eq!(super::normalize("Windows"), "windows")
+        assert_eq!(super::normalize("Windows", true), "windows")
     }
 }
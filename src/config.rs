@@ -1,8 +1,9 @@
 //! Customize line editor
+use std::borrow::Cow;
 use std::default::Default;
 
 /// User preferences
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Config {
     /// Maximum number of entries in History.
     max_history_size: usize, // history_max_entries
@@ -12,6 +13,17 @@ pub struct Config {
     /// When listing completion alternatives, only display
     /// one screen of possibilities at a time.
     completion_prompt_limit: usize,
+    /// Ignore case when inserting the common prefix of the completion
+    /// candidates
+    completion_ignore_case: bool,
+    /// Hyphens and underscores are equivalent in the common prefix
+    completion_map_case: bool,
+    /// Question asked before listing too many candidates
+    completion_prompt: Cow<'static, str>,
+    /// Prompt displayed when the list of candidates is paused
+    completion_more_prompt: Cow<'static, str>,
+    /// Display the circular completion candidate as ghost text
+    completion_preview: bool,
     /// Duration (milliseconds) Rustyline will wait for a character when
     /// reading an ambiguous key sequence.
     keyseq_timeout: i32,
@@ -34,6 +46,19 @@ pub struct Config {
     check_cursor_position: bool,
     /// Bracketed paste on unix platform
     enable_bracketed_paste: bool,
+    /// Shift + movement keys select text
+    enable_selection: bool,
+    /// `*` in front of the prompt when a history entry has been modified
+    mark_modified_lines: bool,
+    /// Discard the edits made to history entries when a line is accepted
+    revert_all_at_newline: bool,
+    /// Display the completion candidates while typing
+    auto_complete: bool,
+    /// Duration (milliseconds) without key press before the candidates are
+    /// displayed
+    auto_complete_delay: u16,
+    /// Maximum number of candidates displayed while typing
+    auto_complete_rows: usize,
 }
 
 impl Config {
@@ -99,6 +124,58 @@ impl Config {
         self.completion_prompt_limit
     }
 
+    /// Tell if case is ignored when inserting the common prefix of the
+    /// completion candidates (like readline `completion-ignore-case`), so
+    /// that `Read` progresses to `readme` with `readme` and `README.md`.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn completion_ignore_case(&self) -> bool {
+        self.completion_ignore_case
+    }
+
+    /// Tell if hyphens and underscores are equivalent in the common prefix
+    /// of the completion candidates (like readline `completion-map-case`,
+    /// used when `completion_ignore_case` is enabled).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn completion_map_case(&self) -> bool {
+        self.completion_map_case
+    }
+
+    /// Question asked before listing more than `completion_prompt_limit`
+    /// candidates: `{}` is replaced by the number of candidates.
+    ///
+    /// By default, `Display all {} possibilities? (y or n)`.
+    #[must_use]
+    pub fn completion_prompt(&self) -> &str {
+        &self.completion_prompt
+    }
+
+    /// Prompt displayed when the list of candidates is paused (used for
+    /// `CompletionType::List` mode). Then `Space` shows the next page,
+    /// `Enter` or `Down` the next line, `b` the previous page, `Up` the
+    /// previous line, `/` filters the candidates and `q` stops the listing.
+    ///
+    /// By default, `--More--`.
+    #[must_use]
+    pub fn completion_more_prompt(&self) -> &str {
+        &self.completion_more_prompt
+    }
+
+    /// Tell if the candidate is previewed as ghost text after the cursor
+    /// (rendered like a hint) instead of replacing the word being completed
+    /// (used for `CompletionType::Circular` mode when the cursor is at the end
+    /// of the line and all candidates extend the word). The line is updated
+    /// only once the candidate is accepted, in a single undoable change.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn completion_preview(&self) -> bool {
+        self.completion_preview
+    }
+
     /// Duration (milliseconds) Rustyline will wait for a character when
     /// reading an ambiguous key sequence (used for `EditMode::Vi` mode on unix
     /// platform).
@@ -192,6 +269,64 @@ impl Config {
     pub fn enable_bracketed_paste(&self) -> bool {
         self.enable_bracketed_paste
     }
+
+    /// Tell if Shift + movement keys extend a selection which can be copied,
+    /// cut or replaced by typing (like in a GUI text field).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn enable_selection(&self) -> bool {
+        self.enable_selection
+    }
+
+    /// Tell if a `*` is displayed in front of the prompt when the history
+    /// entry being edited has been modified.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn mark_modified_lines(&self) -> bool {
+        self.mark_modified_lines
+    }
+
+    /// Tell if the edits made to history entries while browsing history are
+    /// discarded when a line is accepted. Otherwise, modified entries are
+    /// saved in history (like readline with `revert-all-at-newline` off).
+    ///
+    /// By default, it's enabled: history is left untouched.
+    #[must_use]
+    pub fn revert_all_at_newline(&self) -> bool {
+        self.revert_all_at_newline
+    }
+
+    /// Tell if the completion candidates are displayed below the line while
+    /// typing (without pressing Tab). Tab focuses them: then Up and Down
+    /// select a candidate, Tab inserts it. Escape hides them.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn auto_complete(&self) -> bool {
+        self.auto_complete
+    }
+
+    /// Duration (milliseconds) without key press before the completion
+    /// candidates are displayed (used when `auto_complete` is enabled).
+    /// 0 displays them after each key press.
+    ///
+    /// By default, 100ms.
+    #[must_use]
+    pub fn auto_complete_delay(&self) -> u16 {
+        self.auto_complete_delay
+    }
+
+    /// Maximum number of candidates displayed below the line while typing
+    /// (used when `auto_complete` is enabled): the list scrolls to the
+    /// selected one.
+    ///
+    /// By default, 5.
+    #[must_use]
+    pub fn auto_complete_rows(&self) -> usize {
+        self.auto_complete_rows
+    }
 }
 
 impl Default for Config {
@@ -202,6 +337,11 @@ impl Default for Config {
             history_ignore_space: false,
             completion_type: CompletionType::Circular, // TODO Validate
             completion_prompt_limit: 100,
+            completion_ignore_case: false,
+            completion_map_case: false,
+            completion_prompt: Cow::Borrowed("Display all {} possibilities? (y or n)"),
+            completion_more_prompt: Cow::Borrowed("--More--"),
+            completion_preview: false,
             keyseq_timeout: -1,
             edit_mode: EditMode::Emacs,
             auto_add_history: false,
@@ -212,6 +352,12 @@ impl Default for Config {
             indent_size: 2,
             check_cursor_position: false,
             enable_bracketed_paste: true,
+            enable_selection: false,
+            mark_modified_lines: false,
+            revert_all_at_newline: true,
+            auto_complete: false,
+            auto_complete_delay: 100,
+            auto_complete_rows: 5,
         }
     }
 }
@@ -260,6 +406,11 @@ pub enum CompletionType {
     /// When more than one match, list all matches
     /// (like in Bash/Readline).
     List,
+    /// Show all matches in a menu under the input line and select one with
+    /// the arrow keys or (Shift-)Tab, previewing it in the line
+    /// (like zsh `menu-select`).
+    /// Enter accepts the selected match and Escape cancels.
+    Menu,
 
     /// Complete the match using fuzzy search and selection
     /// (like fzf and plugins)
@@ -379,6 +530,57 @@ impl Builder {
         self
     }
 
+    /// Preview the circular completion candidate as ghost text
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn completion_preview(mut self, yes: bool) -> Self {
+        self.set_completion_preview(yes);
+        self
+    }
+
+    /// Ignore case when inserting the common prefix of the completion
+    /// candidates
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn completion_ignore_case(mut self, yes: bool) -> Self {
+        self.set_completion_ignore_case(yes);
+        self
+    }
+
+    /// Hyphens and underscores are equivalent in the common prefix of the
+    /// completion candidates (when case is ignored)
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn completion_map_case(mut self, yes: bool) -> Self {
+        self.set_completion_map_case(yes);
+        self
+    }
+
+    /// Question asked before listing more than `completion_prompt_limit`
+    /// candidates. Each `{}` in `prompt` is replaced by the number of
+    /// candidates; there is no other placeholder nor escaping (`{{` is
+    /// displayed as is).
+    ///
+    /// By default, `Display all {} possibilities? (y or n)`.
+    #[must_use]
+    pub fn completion_prompt(mut self, prompt: impl Into<Cow<'static, str>>) -> Self {
+        self.set_completion_prompt(prompt);
+        self
+    }
+
+    /// Prompt displayed when the list of candidates is paused. It is
+    /// displayed as is: `{}` is not replaced.
+    ///
+    /// By default, `--More--`.
+    #[must_use]
+    pub fn completion_more_prompt(mut self, prompt: impl Into<Cow<'static, str>>) -> Self {
+        self.set_completion_more_prompt(prompt);
+        self
+    }
+
     /// Choose between Emacs or Vi mode.
     #[must_use]
     pub fn edit_mode(mut self, edit_mode: EditMode) -> Self {
@@ -456,6 +658,62 @@ impl Builder {
         self
     }
 
+    /// Enable or disable text selection with Shift + movement keys
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn selection(mut self, yes: bool) -> Self {
+        self.set_selection(yes);
+        self
+    }
+
+    /// Display a `*` in front of the prompt when a history entry is modified
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn mark_modified_lines(mut self, yes: bool) -> Self {
+        self.set_mark_modified_lines(yes);
+        self
+    }
+
+    /// Discard or save the edits made to history entries when a line is
+    /// accepted
+    ///
+    /// By default, edits are discarded.
+    #[must_use]
+    pub fn revert_all_at_newline(mut self, yes: bool) -> Self {
+        self.set_revert_all_at_newline(yes);
+        self
+    }
+
+    /// Display the completion candidates below the line while typing
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn auto_complete(mut self, yes: bool) -> Self {
+        self.set_auto_complete(yes);
+        self
+    }
+
+    /// Duration (milliseconds) without key press before the completion
+    /// candidates are displayed
+    ///
+    /// By default, 100ms.
+    #[must_use]
+    pub fn auto_complete_delay(mut self, delay_ms: u16) -> Self {
+        self.set_auto_complete_delay(delay_ms);
+        self
+    }
+
+    /// Maximum number of candidates displayed below the line while typing
+    ///
+    /// By default, 5.
+    #[must_use]
+    pub fn auto_complete_rows(mut self, rows: usize) -> Self {
+        self.set_auto_complete_rows(rows);
+        self
+    }
+
     /// Builds a `Config` with the settings specified so far.
     #[must_use]
     pub fn build(self) -> Config {
@@ -505,6 +763,41 @@ pub trait Configurer {
         self.config_mut().completion_prompt_limit = completion_prompt_limit;
     }
 
+    /// Ignore case when inserting the common prefix of the completion
+    /// candidates
+    ///
+    /// By default, it's disabled.
+    fn set_completion_ignore_case(&mut self, yes: bool) {
+        self.config_mut().completion_ignore_case = yes;
+    }
+
+    /// Hyphens and underscores are equivalent in the common prefix of the
+    /// completion candidates (when case is ignored)
+    ///
+    /// By default, it's disabled.
+    fn set_completion_map_case(&mut self, yes: bool) {
+        self.config_mut().completion_map_case = yes;
+    }
+
+    /// Question asked before listing more than `completion_prompt_limit`
+    /// candidates. Each `{}` in `prompt` is replaced by the number of
+    /// candidates; there is no other placeholder nor escaping.
+    fn set_completion_prompt(&mut self, prompt: impl Into<Cow<'static, str>>) {
+        self.config_mut().completion_prompt = prompt.into();
+    }
+
+    /// Prompt displayed when the list of candidates is paused, as is.
+    fn set_completion_more_prompt(&mut self, prompt: impl Into<Cow<'static, str>>) {
+        self.config_mut().completion_more_prompt = prompt.into();
+    }
+
+    /// Preview the circular completion candidate as ghost text
+    ///
+    /// By default, it's disabled.
+    fn set_completion_preview(&mut self, yes: bool) {
+        self.config_mut().completion_preview = yes;
+    }
+
     /// Timeout for ambiguous key sequences in milliseconds.
     fn set_keyseq_timeout(&mut self, keyseq_timeout_ms: i32) {
         self.config_mut().keyseq_timeout = keyseq_timeout_ms;
@@ -571,4 +864,48 @@ pub trait Configurer {
     fn enable_bracketed_paste(&mut self, enabled: bool) {
         self.config_mut().enable_bracketed_paste = enabled;
     }
+
+    /// Enable or disable text selection with Shift + movement keys
+    ///
+    /// By default, it's disabled.
+    fn set_selection(&mut self, yes: bool) {
+        self.config_mut().enable_selection = yes;
+    }
+
+    /// Display a `*` in front of the prompt when a history entry is modified
+    ///
+    /// By default, it's disabled.
+    fn set_mark_modified_lines(&mut self, yes: bool) {
+        self.config_mut().mark_modified_lines = yes;
+    }
+
+    /// Discard or save the edits made to history entries when a line is
+    /// accepted
+    ///
+    /// By default, edits are discarded.
+    fn set_revert_all_at_newline(&mut self, yes: bool) {
+        self.config_mut().revert_all_at_newline = yes;
+    }
+
+    /// Display the completion candidates below the line while typing
+    ///
+    /// By default, it's disabled.
+    fn set_auto_complete(&mut self, yes: bool) {
+        self.config_mut().auto_complete = yes;
+    }
+
+    /// Duration (milliseconds) without key press before the completion
+    /// candidates are displayed
+    ///
+    /// By default, 100ms.
+    fn set_auto_complete_delay(&mut self, delay_ms: u16) {
+        self.config_mut().auto_complete_delay = delay_ms;
+    }
+
+    /// Maximum number of candidates displayed below the line while typing
+    ///
+    /// By default, 5.
+    fn set_auto_complete_rows(&mut self, rows: usize) {
+        self.config_mut().auto_complete_rows = rows;
+    }
 }
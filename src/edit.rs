@@ -2,15 +2,18 @@
 
 use log::debug;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
+use std::ops::Range;
 use std::rc::Rc;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthChar;
 
 use super::{Context, Helper, Result};
-use crate::highlight::Highlighter;
+use crate::completion::{glob_expand_word, history_words, split_words, word_start};
+use crate::highlight::{Highlighter, SelectionHighlighter};
 use crate::hint::Hint;
-use crate::history::SearchDirection;
+use crate::history::{History, SearchDirection};
 use crate::keymap::{Anchor, At, CharSearch, Cmd, Movement, RepeatCount, Word};
 use crate::keymap::{InputState, Invoke, Refresher};
 use crate::layout::{Layout, Position};
@@ -28,12 +31,42 @@ pub struct State<'out, 'prompt, H: Helper> {
     pub line: LineBuffer,  // Edited line buffer
     pub layout: Layout,
     saved_line_for_history: LineBuffer, // Current edited line before history browsing
+    initial_line: LineBuffer,           // Line when editing began (revert-line)
     byte_buffer: [u8; 4],
     pub changes: Rc<RefCell<Changeset>>, // changes to line, for undo/redo
     pub helper: Option<&'out H>,
     pub ctx: Context<'out>,          // Give access to history for `hinter`
     pub hint: Option<Box<dyn Hint>>, // last hint displayed
     highlight_char: bool,            // `true` if a char has been highlighted
+    selection: Option<usize>,        // selection anchor (Shift + movement keys)
+    yank_arg: Option<YankArg>,       // last arg inserted by yank-last-arg
+    dabbrev: Option<Dabbrev>,        // last word inserted by dabbrev-expand
+    mark_modified_lines: bool,       // `*` in front of the prompt if modified
+    modified_mark: bool,             // `true` if `*` is displayed
+    // edits kept while browsing history
+    modified_lines: HashMap<usize, ModifiedLine>,
+}
+
+/// Edits made to a history entry (or to the new line), with their own undo
+/// stack, kept while browsing history.
+struct ModifiedLine {
+    line: String,
+    pos: usize,
+    changes: Changeset,
+}
+
+/// Word inserted by `yank-last-arg`, replaced when the command is repeated.
+struct YankArg {
+    history_index: usize, // history entry the word comes from
+    nth: isize,           // word index
+    size: usize,          // inserted text length
+}
+
+/// Word inserted by `dabbrev-expand`, replaced when the command is repeated.
+struct Dabbrev {
+    start: usize,   // start of the word
+    prefix: String, // word typed before the first expansion
+    index: usize,   // index of the inserted word in the matches
 }
 
 enum Info<'m> {
@@ -57,12 +90,19 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             line: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
             layout: Layout::default(),
             saved_line_for_history: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
+            initial_line: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
             byte_buffer: [0; 4],
             changes: Rc::new(RefCell::new(Changeset::new())),
             helper,
             ctx,
             hint: None,
             highlight_char: false,
+            selection: None,
+            yank_arg: None,
+            dabbrev: None,
+            modified_lines: HashMap::new(),
+            mark_modified_lines: false,
+            modified_mark: false,
         }
     }
 
@@ -85,9 +125,7 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             let rc = input_state.next_cmd(rdr, self, single_esc_abort, ignore_external_print);
             if rc.is_err() && self.out.sigwinch() {
                 self.out.update_size();
-                self.prompt_size = self
-                    .out
-                    .calculate_position(self.prompt, Position::default());
+                self.update_prompt_size();
                 self.refresh_line()?;
                 continue;
             }
@@ -103,11 +141,11 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             .update(self.line.as_str(), self.line.pos());
     }
 
-    pub fn restore(&mut self) {
-        self.line.update(
-            self.saved_line_for_history.as_str(),
-            self.saved_line_for_history.pos(),
-        );
+    /// Remember the current line as the one `revert-line` goes back to
+    /// (when not browsing history).
+    pub fn set_initial_line(&mut self) {
+        let (line, pos) = (self.line.as_str(), self.line.pos());
+        self.initial_line.update(line, pos);
     }
 
     pub fn move_cursor(&mut self) -> Result<()> {
@@ -151,6 +189,18 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         default_prompt: bool,
         info: Info<'_>,
     ) -> Result<()> {
+        let marked_prompt;
+        let (prompt, prompt_size) = if default_prompt {
+            self.update_modified_mark();
+            if self.modified_mark {
+                marked_prompt = format!("*{}", prompt);
+                (marked_prompt.as_str(), self.prompt_size)
+            } else {
+                (prompt, self.prompt_size)
+            }
+        } else {
+            (prompt, prompt_size)
+        };
         let info = match info {
             Info::NoHint => None,
             Info::Hint => self.hint.as_ref().map(|h| h.display()),
@@ -161,6 +211,14 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         } else {
             None
         };
+        let selection_highlighter;
+        let highlighter = match self.selection() {
+            Some(range) if self.out.colors_enabled() => {
+                selection_highlighter = SelectionHighlighter::new(highlighter, range);
+                Some(&selection_highlighter as &dyn Highlighter)
+            }
+            _ => highlighter,
+        };
 
         let new_layout = self
             .out
@@ -181,6 +239,44 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         Ok(())
     }
 
+    /// Show `*` in front of the prompt when a history entry has been modified.
+    pub fn set_mark_modified_lines(&mut self, yes: bool) {
+        self.mark_modified_lines = yes;
+    }
+
+    /// Show or hide the modified history entry mark.
+    /// Returns `true` if the mark has been toggled.
+    fn update_modified_mark(&mut self) -> bool {
+        let mark = self.mark_modified_lines
+            && matches!(
+                self.ctx.history.get(self.ctx.history_index),
+                Some(entry) if entry.as_str() != self.line.as_str()
+            );
+        if mark == self.modified_mark {
+            return false;
+        }
+        self.modified_mark = mark;
+        self.update_prompt_size();
+        true
+    }
+
+    /// Number of rows used by the prompt and the line.
+    pub fn line_rows(&self) -> usize {
+        self.out
+            .calculate_position(&self.line, self.prompt_size)
+            .row
+            + 1
+    }
+
+    pub fn update_prompt_size(&mut self) {
+        let orig = if self.modified_mark {
+            self.out.calculate_position("*", Position::default())
+        } else {
+            Position::default()
+        };
+        self.prompt_size = self.out.calculate_position(self.prompt, orig);
+    }
+
     pub fn hint(&mut self) {
         if let Some(hinter) = self.helper {
             let hint = hinter.hint(self.line.as_str(), self.line.pos(), &self.ctx);
@@ -349,6 +445,7 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
                     && self.layout.cursor.col + width < self.out.get_columns()
                     && (self.hint.is_none() && no_previous_hint) // TODO refresh only current line
                     && !self.highlight_char()
+                    && !self.update_modified_mark()
                 {
                     // Avoid a full update of the line in the trivial case.
                     self.layout.cursor.col += width;
@@ -434,6 +531,85 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         result
     }
 
+    /// Insert the `nth` word of the previous history entry.
+    pub fn edit_yank_nth_arg(&mut self, nth: isize) -> Result<()> {
+        let history = self.ctx.history;
+        let arg = self
+            .ctx
+            .history_index
+            .checked_sub(1)
+            .and_then(|index| nth_arg(history, index, nth));
+        match arg {
+            Some(arg) if self.line.yank(arg, 1).is_some() => self.refresh_line(),
+            _ => self.out.beep(),
+        }
+    }
+
+    /// Insert the last (or `nth`) word of the previous history entry.
+    /// When repeated, replace it with the same word of the entry before.
+    pub fn edit_yank_last_arg(&mut self, nth: Option<isize>) -> Result<()> {
+        let history = self.ctx.history;
+        let (index, nth, size) = match self.yank_arg {
+            Some(ref last) => (last.history_index.checked_sub(1), last.nth, last.size),
+            None => (self.ctx.history_index.checked_sub(1), nth.unwrap_or(-1), 0),
+        };
+        let index = match index {
+            Some(index) => index,
+            None => return self.out.beep(),
+        };
+        let arg = nth_arg(history, index, nth);
+        self.yank_arg = Some(YankArg {
+            history_index: index,
+            nth,
+            size: arg.map_or(size, str::len),
+        });
+        match arg {
+            Some(arg) => self.edit_yank_pop(size, arg),
+            None => self.out.beep(),
+        }
+    }
+
+    /// Complete the word before the cursor with a word of the history
+    /// entries, the next one when repeated. After the last one, the typed
+    /// word is restored.
+    pub fn edit_dabbrev_expand(&mut self) -> Result<()> {
+        let (start, prefix, index) = match self.dabbrev.take() {
+            Some(last) => (last.start, last.prefix, last.index + 1),
+            None => {
+                let start = word_start(&self.line, self.line.pos());
+                (start, self.line[start..self.line.pos()].to_owned(), 0)
+            }
+        };
+        let words = history_words(self.ctx.history, &prefix);
+        if words.is_empty() {
+            return self.out.beep();
+        }
+        let index = index % (words.len() + 1);
+        let word = words.get(index).unwrap_or(&prefix);
+        self.changes.borrow_mut().begin();
+        self.line.replace(start..self.line.pos(), word);
+        self.changes.borrow_mut().end();
+        self.dabbrev = Some(Dabbrev {
+            start,
+            prefix,
+            index,
+        });
+        if index == words.len() {
+            self.out.beep()?;
+        }
+        self.refresh_line()
+    }
+
+    /// Forget the word inserted by `dabbrev-expand`.
+    pub fn reset_dabbrev(&mut self) {
+        self.dabbrev = None;
+    }
+
+    /// Forget the word inserted by `yank-last-arg`.
+    pub fn reset_yank_arg(&mut self) {
+        self.yank_arg = None;
+    }
+
     /// Move cursor on the left.
     pub fn edit_move_backward(&mut self, n: RepeatCount) -> Result<()> {
         if self.line.move_backward(n) {
@@ -488,6 +664,67 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         }
     }
 
+    /// Byte range between the selection anchor and the cursor, if any.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.selection?;
+        let pos = self.line.pos();
+        let range = if anchor < pos {
+            anchor..pos
+        } else {
+            pos..anchor
+        };
+        if range.is_empty()
+            || !self.line.is_char_boundary(range.start)
+            || !self.line.is_char_boundary(range.end)
+        {
+            None
+        } else {
+            Some(range)
+        }
+    }
+
+    /// Clear the selection. Returns `true` if there was one.
+    pub fn reset_selection(&mut self) -> bool {
+        self.selection.take().is_some()
+    }
+
+    /// Move the cursor and extend the selection from its anchor (set to the
+    /// current cursor position if there is no selection yet).
+    pub fn edit_select(&mut self, mvt: &Movement) -> Result<()> {
+        let anchor = self.selection.unwrap_or_else(|| self.line.pos());
+        let moved = match *mvt {
+            Movement::BackwardChar(n) => self.line.move_backward(n),
+            Movement::ForwardChar(n) => self.line.move_forward(n),
+            Movement::BeginningOfLine => self.line.move_home(),
+            Movement::EndOfLine => self.line.move_end(),
+            Movement::BackwardWord(n, word_def) => self.line.move_to_prev_word(word_def, n),
+            Movement::ForwardWord(n, at, word_def) => self.line.move_to_next_word(at, word_def, n),
+            Movement::BeginningOfBuffer => self.line.move_buffer_start(),
+            Movement::EndOfBuffer => self.line.move_buffer_end(),
+            Movement::LineUp(n) => self.line.move_to_line_up(n),
+            Movement::LineDown(n) => self.line.move_to_line_down(n),
+            Movement::ViCharSearch(n, cs) => self.line.move_to(cs, n),
+            Movement::ViFirstPrint | Movement::WholeLine | Movement::WholeBuffer => false,
+        };
+        if moved {
+            self.selection = Some(anchor);
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Delete the selected text (without saving it in the kill ring).
+    pub fn edit_delete_selection(&mut self) -> Result<()> {
+        if let Some(range) = self.selection() {
+            self.selection = None;
+            self.line.delete_range(range);
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn edit_kill(&mut self, mvt: &Movement) -> Result<()> {
         if self.line.kill(mvt) {
             self.refresh_line()
@@ -591,29 +828,108 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             return Ok(());
         }
         if self.ctx.history_index == history.len() {
-            if prev {
-                // Save the current edited line before overwriting it
-                self.backup();
-            } else {
+            if !prev {
                 return Ok(());
             }
         } else if self.ctx.history_index == 0 && prev {
             return Ok(());
         }
-        if prev {
-            self.ctx.history_index -= 1;
+        let index = if prev {
+            self.ctx.history_index - 1
         } else {
-            self.ctx.history_index += 1;
+            self.ctx.history_index + 1
+        };
+        self.load_history_entry(index, None);
+        self.refresh_line()
+    }
+
+    /// Save the edited line (and its undo stack) then substitute it with the
+    /// history entry at `index` (or with the new line being edited if `index`
+    /// is the history length). Previous edits of this entry are restored.
+    fn load_history_entry(&mut self, index: usize, pos: Option<usize>) {
+        let history = self.ctx.history;
+        let current = self.ctx.history_index;
+        if current == history.len() {
+            // Save the current edited line before overwriting it
+            self.backup();
         }
-        if self.ctx.history_index < history.len() {
-            let buf = history.get(self.ctx.history_index).unwrap();
-            self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
-            self.changes.borrow_mut().end();
+        let changes = std::mem::replace(&mut *self.changes.borrow_mut(), Changeset::new());
+        if current == history.len()
+            || matches!(history.get(current), Some(entry) if entry.as_str() != self.line.as_str())
+        {
+            let modified = ModifiedLine {
+                line: self.line.as_str().to_owned(),
+                pos: self.line.pos(),
+                changes,
+            };
+            self.modified_lines.insert(current, modified);
         } else {
-            // Restore current edited line
-            self.restore();
+            self.modified_lines.remove(&current);
+        }
+        self.ctx.history_index = index;
+        let (line, pos, changes) = match self.modified_lines.remove(&index) {
+            Some(modified) => {
+                let pos = pos.unwrap_or(modified.pos).min(modified.line.len());
+                (modified.line, pos, modified.changes)
+            }
+            None => match history.get(index) {
+                Some(entry) => (entry.clone(), pos.unwrap_or(entry.len()), Changeset::new()),
+                None => {
+                    // Restore current edited line
+                    let saved = &self.saved_line_for_history;
+                    (saved.as_str().to_owned(), saved.pos(), Changeset::new())
+                }
+            },
+        };
+        // loading a line is not an undoable change (changes is borrowed)
+        let mut current_changes = self.changes.borrow_mut();
+        self.line.update(&line, pos);
+        *current_changes = changes;
+    }
+
+    /// History entries modified while browsing history (excluding the one
+    /// being edited).
+    pub fn take_modified_lines(&mut self) -> Vec<(usize, String)> {
+        let history = self.ctx.history;
+        let current = self.ctx.history_index;
+        self.modified_lines
+            .drain()
+            .filter(|(index, modified)| {
+                *index != current
+                    && matches!(history.get(*index), Some(entry) if *entry != modified.line)
+            })
+            .map(|(index, modified)| (index, modified.line))
+            .collect()
+    }
+
+    /// Undo all changes made to this line: restore the history entry being
+    /// browsed or the line as it was when editing began.
+    pub fn edit_revert_line(&mut self) -> Result<()> {
+        let history = self.ctx.history;
+        let (original, pos) = match history.get(self.ctx.history_index) {
+            Some(entry) => (entry.as_str(), entry.len()),
+            None => (self.initial_line.as_str(), self.initial_line.pos()),
+        };
+        if self.line.as_str() == original {
+            return Ok(());
         }
+        let original = original.to_owned();
+        self.changes.borrow_mut().begin();
+        self.line.update(&original, pos);
+        self.changes.borrow_mut().end();
+        self.refresh_line()
+    }
+
+    /// Replace the glob word before the cursor by the matching paths, as
+    /// one undo step (glob-expand-word).
+    pub fn edit_glob_expand_word(&mut self) -> Result<()> {
+        let (start, paths) = match glob_expand_word(&self.line, self.line.pos()) {
+            Some(expansion) => expansion,
+            None => return self.out.beep(),
+        };
+        self.changes.borrow_mut().begin();
+        self.line.replace(start..self.line.pos(), &paths);
+        self.changes.borrow_mut().end();
         self.refresh_line()
     }
 
@@ -628,20 +944,13 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         {
             return self.out.beep();
         }
-        if dir == SearchDirection::Reverse {
-            self.ctx.history_index -= 1;
+        let start = if dir == SearchDirection::Reverse {
+            self.ctx.history_index - 1
         } else {
-            self.ctx.history_index += 1;
-        }
-        if let Some(sr) = history.starts_with(
-            &self.line.as_str()[..self.line.pos()],
-            self.ctx.history_index,
-            dir,
-        ) {
-            self.ctx.history_index = sr.idx;
-            self.changes.borrow_mut().begin();
-            self.line.update(sr.entry, sr.pos);
-            self.changes.borrow_mut().end();
+            self.ctx.history_index + 1
+        };
+        if let Some(sr) = history.starts_with(&self.line.as_str()[..self.line.pos()], start, dir) {
+            self.load_history_entry(sr.idx, Some(sr.pos));
             self.refresh_line()
         } else {
             self.out.beep()
@@ -655,25 +964,16 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             return Ok(());
         }
         if self.ctx.history_index == history.len() {
-            if first {
-                // Save the current edited line before overwriting it
-                self.backup();
-            } else {
+            if !first {
                 return Ok(());
             }
         } else if self.ctx.history_index == 0 && first {
             return Ok(());
         }
         if first {
-            self.ctx.history_index = 0;
-            let buf = history.get(self.ctx.history_index).unwrap();
-            self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
-            self.changes.borrow_mut().end();
+            self.load_history_entry(0, None);
         } else {
-            self.ctx.history_index = history.len();
-            // Restore current edited line
-            self.restore();
+            self.load_history_entry(history.len(), None);
         }
         self.refresh_line()
     }
@@ -688,6 +988,18 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     }
 }
 
+/// `nth` word of the history entry at `index` (negative `nth` counts from
+/// the end).
+fn nth_arg(history: &History, index: usize, nth: isize) -> Option<&str> {
+    let words = split_words(history.get(index)?);
+    let i = if nth < 0 {
+        words.len().checked_sub(nth.unsigned_abs())?
+    } else {
+        nth.unsigned_abs()
+    };
+    words.get(i).copied()
+}
+
 #[cfg(test)]
 pub fn init_state<'out, H: Helper>(
     out: &'out mut <Terminal as Term>::Writer,
@@ -703,12 +1015,19 @@ pub fn init_state<'out, H: Helper>(
         line: LineBuffer::init(line, pos, None),
         layout: Layout::default(),
         saved_line_for_history: LineBuffer::with_capacity(100),
+        initial_line: LineBuffer::with_capacity(100),
         byte_buffer: [0; 4],
         changes: Rc::new(RefCell::new(Changeset::new())),
         helper,
         ctx: Context::new(history),
         hint: Some(Box::new("hint".to_owned())),
         highlight_char: false,
+        selection: None,
+        yank_arg: None,
+        dabbrev: None,
+        modified_lines: HashMap::new(),
+        mark_modified_lines: false,
+        modified_mark: false,
     }
 }
 
@@ -756,4 +1075,28 @@ mod test {
         assert_eq!(2, s.ctx.history_index);
         assert_eq!(line, s.line.as_str());
     }
+
+    #[test]
+    fn mark_modified_lines() {
+        let mut out = Sink::default();
+        let mut history = History::new();
+        history.add("line0");
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+        s.ctx.history_index = history.len();
+        s.set_mark_modified_lines(true);
+
+        s.edit_history_next(true).unwrap();
+        assert_eq!(0, s.prompt_size.col);
+        s.edit_insert('X', 1).unwrap();
+        assert_eq!("line0X", s.line.as_str());
+        assert_eq!(1, s.prompt_size.col);
+        assert_eq!(s.layout.cursor.col, 7);
+
+        s.edit_history_next(false).unwrap();
+        assert_eq!(0, s.prompt_size.col);
+        s.edit_history_next(true).unwrap();
+        assert_eq!("line0X", s.line.as_str());
+        assert_eq!(1, s.prompt_size.col);
+    }
 }
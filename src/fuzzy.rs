@@ -0,0 +1,198 @@
+//! Fuzzy matching (subsequence with gap penalties)
+//!
+//! Used to filter and rank completion candidates (see
+//! [`FuzzyCompleter`](crate::completion::FuzzyCompleter)) and to search
+//! history (see [`History::fuzzy_search`](crate::history::History::fuzzy_search)).
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+// Characters before the first match
+const SCORE_GAP_LEADING: i64 = -1;
+const MAX_GAP_LEADING: i64 = 3;
+const BONUS_BOUNDARY: i64 = SCORE_MATCH / 2;
+const BONUS_CAMEL: i64 = BONUS_BOUNDARY - 1;
+const BONUS_CONSECUTIVE: i64 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+// The first char of the pattern is more significant
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+/// Fuzzy match
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    /// The higher, the better
+    pub score: i64,
+    /// Byte offsets of the matched characters in the text
+    pub positions: Vec<usize>,
+}
+
+/// Match all the characters of `pattern` in `text`, in order but not
+/// necessarily contiguous.
+///
+/// Matching is case insensitive unless `pattern` contains an uppercase
+/// character (smart case).
+/// Consecutive matches and matches at the start of a word (after a space,
+/// `/`, `_`, `-`, `.` or on a camelCase hump) score higher, gaps are
+/// penalized.
+///
+/// Return `None` if `text` doesn't match.
+#[must_use]
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<Match> {
+    if pattern.is_empty() {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let eq = |p: char, t: char| {
+        if case_sensitive {
+            p == t
+        } else {
+            p == t || t.to_lowercase().eq(p.to_lowercase())
+        }
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<(usize, char)> = text.char_indices().collect();
+    // quick check: is `pattern` a subsequence of `text` ?
+    let mut p = pattern.iter().peekable();
+    for &(_, t) in &text {
+        if let Some(&&c) = p.peek() {
+            if eq(c, t) {
+                p.next();
+            }
+        }
+    }
+    if p.peek().is_some() {
+        return None;
+    }
+
+    let bonus: Vec<i64> = (0..text.len())
+        .map(|j| {
+            let c = text[j].1;
+            match j.checked_sub(1).map(|k| text[k].1) {
+                None => BONUS_BOUNDARY,
+                Some(' ' | '/' | '\\' | '_' | '-' | '.' | ':') => BONUS_BOUNDARY,
+                Some(prev) if prev.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+                Some(prev) if !prev.is_numeric() && c.is_numeric() => BONUS_CAMEL,
+                _ => 0,
+            }
+        })
+        .collect();
+
+    // scores[i][j]: best score with `pattern[i]` matched at `text[j]`
+    // from[i][j]: where `pattern[i - 1]` is matched in this case
+    // chunk[i][j]: bonus of the first char of consecutive matches, which is
+    // inherited by the following ones
+    let (n, m) = (pattern.len(), text.len());
+    let mut scores: Vec<Vec<Option<i64>>> = vec![vec![None; m]; n];
+    let mut from: Vec<Vec<usize>> = vec![vec![0; m]; n];
+    let mut chunk: Vec<Vec<i64>> = vec![vec![0; m]; n];
+    for (i, &c) in pattern.iter().enumerate() {
+        // best (score, index) of a previous match followed by a gap
+        let mut gap: Option<(i64, usize)> = None;
+        for j in 0..m {
+            if i > 0 && j >= 2 {
+                gap = gap.map(|(s, k)| (s + SCORE_GAP_EXTENSION, k));
+                if let Some(s) = scores[i - 1][j - 2] {
+                    let s = s + SCORE_GAP_START;
+                    if !matches!(gap, Some((g, _)) if g >= s) {
+                        gap = Some((s, j - 2));
+                    }
+                }
+            }
+            if !eq(c, text[j].1) {
+                continue;
+            }
+            if i == 0 {
+                scores[i][j] = Some(
+                    SCORE_MATCH
+                        + bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER
+                        + SCORE_GAP_LEADING * (j as i64).min(MAX_GAP_LEADING),
+                );
+                chunk[i][j] = bonus[j];
+                continue;
+            }
+            let consecutive = match j.checked_sub(1) {
+                Some(k) => scores[i - 1][k].map(|s| {
+                    let b = bonus[j].max(chunk[i - 1][k]).max(BONUS_CONSECUTIVE);
+                    (s + SCORE_MATCH + b, k, b)
+                }),
+                None => None,
+            };
+            let gapped = gap.map(|(s, k)| (s + SCORE_MATCH + bonus[j], k, bonus[j]));
+            let best = match (consecutive, gapped) {
+                (Some(c), Some(g)) => Some(if c.0 >= g.0 { c } else { g }),
+                (c, g) => c.or(g),
+            };
+            if let Some((s, k, b)) = best {
+                scores[i][j] = Some(s);
+                from[i][j] = k;
+                chunk[i][j] = b;
+            }
+        }
+    }
+
+    let (score, mut j) = scores[n - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (s, j)))
+        // first best match
+        .fold(None, |best: Option<(i64, usize)>, (s, j)| match best {
+            Some((b, _)) if b >= s => best,
+            _ => Some((s, j)),
+        })?;
+    let mut positions = vec![0; n];
+    for i in (0..n).rev() {
+        positions[i] = text[j].0;
+        j = from[i][j];
+    }
+    Some(Match { score, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_match;
+
+    fn positions(pattern: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(pattern, text).map(|m| m.positions)
+    }
+
+    fn score(pattern: &str, text: &str) -> i64 {
+        fuzzy_match(pattern, text).unwrap().score
+    }
+
+    #[test]
+    fn no_match() {
+        assert_eq!(None, fuzzy_match("abc", "acb"));
+        assert_eq!(None, fuzzy_match("a", ""));
+        assert_eq!(None, fuzzy_match("A", "a"));
+    }
+
+    #[test]
+    fn match_positions() {
+        assert_eq!(Some(vec![]), positions("", "abc"));
+        assert_eq!(Some(vec![0, 1, 2]), positions("abc", "abc"));
+        assert_eq!(Some(vec![0, 4, 8]), positions("abc", "a_c_b_a_c"));
+        // word boundaries are preferred
+        assert_eq!(Some(vec![5, 9]), positions("fb", "xfoo_foo_bar"));
+        // consecutive matches are preferred
+        assert_eq!(Some(vec![6, 7, 8]), positions("bar", "bxaxr_bar"));
+        // byte offsets
+        assert_eq!(Some(vec![0, 4]), positions("éa", "ébca"));
+    }
+
+    #[test]
+    fn smart_case() {
+        assert_eq!(Some(vec![0]), positions("f", "Foo"));
+        assert_eq!(Some(vec![3]), positions("F", "fooFoo"));
+    }
+
+    #[test]
+    fn ranking() {
+        assert!(score("abc", "abc") > score("abc", "abxc"));
+        assert!(score("abc", "abxc") > score("abc", "axxbxxc"));
+        assert!(score("fb", "foo_bar") > score("fb", "foobar"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+        assert!(score("foo", "foo") > score("foo", "xxxxxfoo"));
+    }
+}
@@ -1,9 +1,13 @@
 //! Syntax highlighting
 
+use crate::completion::FileKind;
 use crate::config::CompletionType;
 use memchr::memchr;
 use std::borrow::Cow::{self, Borrowed, Owned};
 use std::cell::Cell;
+use std::collections::HashMap;
+use std::env;
+use std::ops::Range;
 
 /// Syntax highlighter with [ANSI color](https://en.wikipedia.org/wiki/ANSI_escape_code#SGR_(Select_Graphic_Rendition)_parameters).
 /// Rustyline will try to handle escape sequence for ANSI color on windows
@@ -39,7 +43,8 @@ pub trait Highlighter {
     /// Takes the completion `candidate` and
     /// returns the highlighted version (with ANSI color).
     ///
-    /// Currently, used only with `CompletionType::List`.
+    /// Currently, used only with `CompletionType::List` and
+    /// `CompletionType::Menu`.
     fn highlight_candidate<'c>(
         &self,
         candidate: &'c str, // FIXME should be Completer::Candidate
@@ -91,6 +96,66 @@ impl<'r, H: ?Sized + Highlighter> Highlighter for &'r H {
     }
 }
 
+/// Show the selected text in reverse video.
+///
+/// The line is rendered without the wrapped highlighter's colors while a
+/// selection is active (their escape sequences would shift the selection
+/// byte offsets). Prompt, hint and candidates are delegated as is.
+pub(crate) struct SelectionHighlighter<'h> {
+    inner: Option<&'h dyn Highlighter>,
+    selection: Range<usize>,
+}
+
+impl<'h> SelectionHighlighter<'h> {
+    pub fn new(inner: Option<&'h dyn Highlighter>, selection: Range<usize>) -> Self {
+        Self { inner, selection }
+    }
+}
+
+impl Highlighter for SelectionHighlighter<'_> {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let Range { start, end } = self.selection;
+        if start >= end || !line.is_char_boundary(start) || !line.is_char_boundary(end) {
+            return Borrowed(line);
+        }
+        Owned(format!(
+            "{}\x1b[7m{}\x1b[0m{}",
+            &line[..start],
+            &line[start..end],
+            &line[end..]
+        ))
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        match self.inner {
+            Some(inner) => inner.highlight_prompt(prompt, default),
+            None => Borrowed(prompt),
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        match self.inner {
+            Some(inner) => inner.highlight_hint(hint),
+            None => Borrowed(hint),
+        }
+    }
+
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        completion: CompletionType,
+    ) -> Cow<'c, str> {
+        match self.inner {
+            Some(inner) => inner.highlight_candidate(candidate, completion),
+            None => Borrowed(candidate),
+        }
+    }
+}
+
 const OPENS: &[u8; 3] = b"{[(";
 const CLOSES: &[u8; 3] = b"}])";
 
@@ -135,6 +200,100 @@ impl Highlighter for MatchingBracketHighlighter {
     }
 }
 
+// GNU `dircolors` defaults
+const DEFAULT_LS_COLORS: &str =
+    "rs=0:di=01;34:ln=01;36:pi=40;33:so=01;35:do=01;35:bd=40;33;01:cd=40;33;01:or=40;31;01:ex=01;32";
+
+/// File name colors, as specified by the `LS_COLORS` environment variable
+/// (like GNU `ls` or bash `colored-stats`).
+///
+/// Used by [`FilenameCompleter`](crate::completion::FilenameCompleter) to
+/// color its candidates.
+#[derive(Clone, Debug, Default)]
+pub struct LsColors {
+    types: HashMap<String, String>,  // `di`, `ln`, `ex`, ...
+    suffixes: Vec<(String, String)>, // `*.rs`, `*README`, ...
+}
+
+impl LsColors {
+    /// Parses `LS_COLORS`, or uses the GNU `dircolors` defaults when it is
+    /// not set.
+    #[must_use]
+    pub fn from_env() -> Self {
+        match env::var("LS_COLORS") {
+            Ok(spec) if !spec.is_empty() => Self::parse(&spec),
+            _ => Self::parse(DEFAULT_LS_COLORS),
+        }
+    }
+
+    /// Parses a `LS_COLORS` specification, like `di=01;34:ex=01;32:*.rs=33`.
+    /// Invalid entries are ignored.
+    #[must_use]
+    pub fn parse(spec: &str) -> Self {
+        let mut ls_colors = Self::default();
+        for entry in spec.split(':') {
+            let (key, style) = match entry.split_once('=') {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if let Some(suffix) = key.strip_prefix('*') {
+                ls_colors
+                    .suffixes
+                    .push((suffix.to_owned(), style.to_owned()));
+            } else {
+                ls_colors.types.insert(key.to_owned(), style.to_owned());
+            }
+        }
+        ls_colors
+    }
+
+    /// SGR parameters of the file `name` of the given `kind`, if colored.
+    #[must_use]
+    pub fn style(&self, name: &str, kind: Option<FileKind>) -> Option<&str> {
+        let key = match kind {
+            Some(FileKind::Directory) => "di",
+            Some(FileKind::Symlink) => "ln",
+            Some(FileKind::OrphanSymlink) => "or",
+            Some(FileKind::Fifo) => "pi",
+            Some(FileKind::Socket) => "so",
+            Some(FileKind::BlockDevice) => "bd",
+            Some(FileKind::CharDevice) => "cd",
+            Some(FileKind::Executable) => "ex",
+            Some(FileKind::File) | None => "fi",
+        };
+        let style = match self.types.get(key).filter(|_| key != "fi") {
+            Some(style) => style,
+            // regular files: the last matching suffix takes precedence
+            None if matches!(kind, Some(FileKind::File | FileKind::Executable) | None) => self
+                .suffixes
+                .iter()
+                .rev()
+                .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+                .map(|(_, style)| style)
+                .or_else(|| self.types.get("fi"))?,
+            None => return None,
+        };
+        // `ln=target`: colored as the target, which is unknown here
+        if matches!(style.as_str(), "" | "0" | "00" | "target") {
+            None
+        } else {
+            Some(style)
+        }
+    }
+
+    /// Colors the file `name` of the given `kind`.
+    #[must_use]
+    pub fn paint<'n>(&self, name: &'n str, kind: Option<FileKind>) -> Cow<'n, str> {
+        match self.style(name, kind) {
+            Some(style) => {
+                let reset = self.types.get("rs").map_or("0", String::as_str);
+                Owned(format!("\x1b[{}m{}\x1b[{}m", style, name, reset))
+            }
+            None => Borrowed(name),
+        }
+    }
+}
+
 fn find_matching_bracket(line: &str, pos: usize, bracket: u8) -> Option<(u8, usize)> {
     let matching = matching_bracket(bracket);
     let mut idx;
@@ -269,6 +428,42 @@ mod tests {
         assert_eq!(matching_bracket(b')'), b'(');
     }
 
+    #[test]
+    pub fn selection() {
+        use super::{Highlighter, SelectionHighlighter};
+        let highlighter = SelectionHighlighter::new(None, 1..3);
+        assert_eq!(highlighter.highlight("abcd", 3), "a\x1b[7mbc\x1b[0md");
+        let highlighter = SelectionHighlighter::new(None, 1..2);
+        assert_eq!(highlighter.highlight("aé", 2), "aé");
+    }
+
+    #[test]
+    pub fn ls_colors() {
+        use super::LsColors;
+        use crate::completion::FileKind;
+        let ls_colors =
+            LsColors::parse("di=01;34:ln=target:ex=01;32:*.rs=33:*.tar.gz=31:*.gz=35:fi=");
+        assert_eq!(
+            Some("01;34"),
+            ls_colors.style("src", Some(FileKind::Directory))
+        );
+        assert_eq!(None, ls_colors.style("link", Some(FileKind::Symlink)));
+        assert_eq!(Some("33"), ls_colors.style("lib.rs", Some(FileKind::File)));
+        // executable wins over the suffix
+        assert_eq!(
+            Some("01;32"),
+            ls_colors.style("run.rs", Some(FileKind::Executable))
+        );
+        // the last matching suffix wins
+        assert_eq!(Some("35"), ls_colors.style("a.tar.gz", None));
+        assert_eq!(None, ls_colors.style("Cargo.toml", Some(FileKind::File)));
+        assert_eq!(
+            "\x1b[01;34msrc\x1b[0m",
+            ls_colors.paint("src", Some(FileKind::Directory))
+        );
+        assert_eq!("Cargo.toml", ls_colors.paint("Cargo.toml", None));
+    }
+
     #[test]
     pub fn is_open_bracket() {
         use super::is_close_bracket;
@@ -13,6 +13,7 @@ use std::time::SystemTime;
 
 use super::Result;
 use crate::config::{Config, HistoryDuplicates};
+use crate::fuzzy::fuzzy_match;
 
 /// Search direction
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -49,6 +50,9 @@ pub struct History {
     pub(crate) ignore_dups: bool,
     /// Number of entries inputted by user and not saved yet
     new_entries: usize,
+    /// Edits (original, edited) of already saved entries, applied to the
+    /// file content by `append`
+    edits: Vec<(String, String)>,
     /// last path used by either `load` or `save`
     path_info: Option<PathInfo>,
 }
@@ -79,6 +83,7 @@ impl History {
             ignore_space: config.history_ignore_space(),
             ignore_dups: config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive,
             new_entries: 0,
+            edits: Vec::new(),
             path_info: None,
         }
     }
@@ -89,6 +94,30 @@ impl History {
         self.entries.get(index)
     }
 
+    /// Replace the history entry at position `index` (used to save the edits
+    /// made while browsing history).
+    ///
+    /// As the entry may already be in the history file, the next
+    /// [`append`](History::append) reloads the file and replaces the
+    /// (last) original entry there, keeping the entries appended by other
+    /// sessions.
+    pub(crate) fn set(&mut self, index: usize, line: String) {
+        let saved = index < self.entries.len().saturating_sub(self.new_entries);
+        if let Some(entry) = self.entries.get_mut(index) {
+            if *entry == line {
+                return;
+            }
+            if saved {
+                // an entry may be edited several times
+                match self.edits.iter_mut().find(|(_, edited)| edited == entry) {
+                    Some((_, edited)) => *edited = line.clone(),
+                    None => self.edits.push((entry.clone(), line.clone())),
+                }
+            }
+            *entry = line;
+        }
+    }
+
     /// Return the last history entry (i.e. previous command)
     #[must_use]
     pub fn last(&self) -> Option<&String> {
@@ -155,7 +184,7 @@ impl History {
     // TODO history_truncate_file
     // https://tiswww.case.edu/php/chet/readline/history.html#IDX31
     pub fn save<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
-        if self.is_empty() || self.new_entries == 0 {
+        if self.is_empty() || (self.new_entries == 0 && self.edits.is_empty()) {
             return Ok(());
         }
         let path = path.as_ref();
@@ -167,6 +196,7 @@ impl History {
         let lock_guard = lock.write()?;
         self.save_to(&lock_guard, false)?;
         self.new_entries = 0;
+        self.edits.clear();
         self.update_path(path, &lock_guard, self.len())
     }
 
@@ -212,7 +242,7 @@ impl History {
     pub fn append<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
         use std::io::Seek;
 
-        if self.is_empty() || self.new_entries == 0 {
+        if self.is_empty() || (self.new_entries == 0 && self.edits.is_empty()) {
             return Ok(());
         }
         let path = path.as_ref();
@@ -222,7 +252,7 @@ impl History {
         let file = OpenOptions::new().write(true).read(true).open(path)?;
         let mut lock = RwLock::new(file);
         let mut lock_guard = lock.write()?;
-        if self.can_just_append(path, &lock_guard)? {
+        if self.edits.is_empty() && self.can_just_append(path, &lock_guard)? {
             lock_guard.seek(SeekFrom::End(0))?;
             self.save_to(&lock_guard, true)?;
             let size = self
@@ -234,16 +264,23 @@ impl History {
             self.new_entries = 0;
             return self.update_path(path, &lock_guard, size);
         }
-        // we may need to truncate file before appending new entries
+        // we may need to truncate file (or apply edits) before appending new
+        // entries
         let mut other = Self {
             entries: VecDeque::new(),
             max_len: self.max_len,
             ignore_space: self.ignore_space,
             ignore_dups: self.ignore_dups,
             new_entries: 0,
+            edits: Vec::new(),
             path_info: None,
         };
         other.load_from(&lock_guard)?;
+        for (original, edited) in self.edits.drain(..) {
+            if let Some(entry) = other.entries.iter_mut().rev().find(|e| **e == original) {
+                *entry = edited;
+            }
+        }
         let first_new_entry = self.entries.len().saturating_sub(self.new_entries);
         for entry in self.entries.iter().skip(first_new_entry) {
             other.add(entry);
@@ -385,6 +422,7 @@ impl History {
     pub fn clear(&mut self) {
         self.entries.clear();
         self.new_entries = 0;
+        self.edits.clear();
     }
 
     /// Search history (start position inclusive [0, len-1]).
@@ -455,6 +493,23 @@ impl History {
         }
     }
 
+    /// Fuzzy search (see [`fuzzy_match`]):
+    /// all the characters of `term` must appear in order in the entry.
+    ///
+    /// Return the nearest matching entry with the position of its first
+    /// matched character.
+    #[must_use]
+    pub fn fuzzy_search(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+    ) -> Option<SearchResult<'_>> {
+        let test =
+            |entry: &str| fuzzy_match(term, entry).and_then(|m| m.positions.first().copied());
+        self.search_match(term, start, dir, test)
+    }
+
     fn search_match<F>(
         &self,
         term: &str,
@@ -596,7 +651,7 @@ mod tests {
     #[test]
     fn add() {
         let config = Config::builder().history_ignore_space(true).build();
-        let mut history = History::with_config(config);
+        let mut history = History::with_config(config.clone());
         assert_eq!(config.max_history_size(), history.max_len);
         assert!(history.add("line1"));
         assert!(history.add("line2"));
@@ -691,6 +746,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn append_modified() -> Result<()> {
+        let mut history = init();
+        let tf = tempfile::NamedTempFile::new()?;
+        history.append(tf.path())?;
+
+        // appended by another session
+        let mut history2 = History::new();
+        history2.load(tf.path())?;
+        history2.add("line4");
+        history2.append(tf.path())?;
+
+        history.set(1, "line2 modified".to_owned());
+        history.set(1, "line2 modified twice".to_owned());
+        history.append(tf.path())?;
+
+        let mut history3 = History::new();
+        history3.load(tf.path())?;
+        assert_eq!(history3.len(), 4);
+        assert_eq!(history3.entries[1], "line2 modified twice");
+        assert_eq!(history3.entries[3], "line4");
+
+        tf.close()?;
+        Ok(())
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
     fn truncate() -> Result<()> {
@@ -749,6 +831,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fuzzy_search() {
+        let history = init();
+        assert_eq!(None, history.fuzzy_search("", 2, SearchDirection::Reverse));
+        assert_eq!(
+            None,
+            history.fuzzy_search("ln4", 2, SearchDirection::Reverse)
+        );
+
+        assert_eq!(
+            Some(SearchResult {
+                idx: 1,
+                entry: history.get(1).unwrap(),
+                pos: 0
+            }),
+            history.fuzzy_search("ln2", 2, SearchDirection::Reverse)
+        );
+        assert_eq!(
+            Some(SearchResult {
+                idx: 2,
+                entry: history.get(2).unwrap(),
+                pos: 1
+            }),
+            history.fuzzy_search("i3", 0, SearchDirection::Forward)
+        );
+    }
+
     #[test]
     fn reverse_search() {
         let history = init();
@@ -36,8 +36,17 @@ pub enum Cmd {
     CompleteBackward,
     /// complete-hint
     CompleteHint,
+    /// copy-region-as-kill (when selection is enabled)
+    CopySelection,
+    /// kill-region (when selection is enabled)
+    CutSelection,
     /// Dedent current line
     Dedent(Movement),
+    /// dabbrev-expand
+    ///
+    /// Complete the word before the cursor with the words of the history
+    /// entries, most recent first. Repeated, replace it with the next match.
+    DabbrevExpand,
     /// downcase-word
     DowncaseWord,
     /// vi-eof-maybe
@@ -46,6 +55,10 @@ pub enum Cmd {
     EndOfHistory,
     /// forward-search-history (incremental search)
     ForwardSearchHistory,
+    /// glob-expand-word
+    ///
+    /// Replace the word before the cursor by the paths matching it.
+    GlobExpandWord,
     /// history-search-backward (common prefix search)
     HistorySearchBackward,
     /// history-search-forward (common prefix search)
@@ -74,12 +87,19 @@ pub enum Cmd {
     PreviousHistory,
     /// quoted-insert
     QuotedInsert,
+    /// redo
+    Redo(RepeatCount),
     /// vi-change-char
     ReplaceChar(RepeatCount, char),
     /// vi-change-to, vi-substitute
     Replace(Movement, Option<String>),
     /// reverse-search-history (incremental search)
     ReverseSearchHistory,
+    /// revert-line
+    RevertLine,
+    /// Extend the selection up to the movement destination (Shift + movement
+    /// keys, when selection is enabled)
+    Select(Movement),
     /// self-insert
     SelfInsert(RepeatCount, char),
     /// Suspend signal (Ctrl-Z on unix platform)
@@ -98,6 +118,17 @@ pub enum Cmd {
     ViYankTo(Movement),
     /// yank, vi-put
     Yank(RepeatCount, Anchor),
+    /// yank-last-arg
+    ///
+    /// Without numeric argument (`None`), insert the last word of the previous
+    /// history entry. Otherwise, behave like `YankNthArg`. Repeated, replace
+    /// it with the same word of the entry before.
+    YankLastArg(Option<isize>),
+    /// yank-nth-arg
+    ///
+    /// Insert the nth word of the previous history entry (0 is the first word,
+    /// negative values count from the last one).
+    YankNthArg(isize),
     /// yank-pop
     YankPop,
     /// moves cursor to the line above or switches to prev history entry if
@@ -160,7 +191,7 @@ impl Cmd {
 
     const fn is_repeatable(&self) -> bool {
         match *self {
-            Cmd::Move(_) => true,
+            Cmd::Move(_) | Cmd::Redo(_) => true,
             _ => self.is_repeatable_change(),
         }
     }
@@ -175,6 +206,7 @@ impl Cmd {
             }
             Cmd::Kill(ref mvt) => Cmd::Kill(mvt.redo(new)),
             Cmd::Move(ref mvt) => Cmd::Move(mvt.redo(new)),
+            Cmd::Redo(previous) => Cmd::Redo(repeat_count(previous, new)),
             Cmd::ReplaceChar(previous, c) => Cmd::ReplaceChar(repeat_count(previous, new), c),
             Cmd::Replace(ref mvt, ref text) => {
                 if text.is_none() {
@@ -350,6 +382,7 @@ pub struct InputState<'b> {
     pub(crate) input_mode: InputMode, // vi only ?
     // numeric arguments: http://web.mit.edu/gnu/doc/html/rlman_1.html#SEC7
     num_args: i16,
+    enable_selection: bool,
     last_cmd: Cmd,                        // vi only
     last_char_search: Option<CharSearch>, // vi only
 }
@@ -403,6 +436,7 @@ impl<'b> InputState<'b> {
             custom_bindings,
             input_mode: InputMode::Insert,
             num_args: 0,
+            enable_selection: config.enable_selection(),
             last_cmd: Cmd::Noop,
             last_char_search: None,
         }
@@ -515,10 +549,13 @@ impl<'b> InputState<'b> {
         wrt: &mut dyn Refresher,
         mut key: KeyEvent,
     ) -> Result<Cmd> {
+        let mut arg = None; // explicit numeric argument (even Meta-0)
         if let E(K::Char(digit @ '-'), M::ALT) = key {
             key = self.emacs_digit_argument(rdr, wrt, digit)?;
+            arg = Some(isize::from(self.num_args));
         } else if let E(K::Char(digit @ '0'..='9'), M::ALT) = key {
             key = self.emacs_digit_argument(rdr, wrt, digit)?;
+            arg = Some(isize::from(self.num_args));
         }
         let (n, positive) = self.emacs_num_args(); // consume them in all cases
 
@@ -588,6 +625,7 @@ impl<'b> InputState<'b> {
                     match snd_key {
                         E(K::Char('G'), M::CTRL) | E::ESC => Cmd::Abort,
                         E(K::Char('U'), M::CTRL) => Cmd::Undo(n),
+                        E(K::Char('*'), M::NONE) => Cmd::GlobExpandWord,
                         E(K::Backspace, M::NONE) => Cmd::Kill(if positive {
                             Movement::BeginningOfLine
                         } else {
@@ -647,9 +685,12 @@ impl<'b> InputState<'b> {
             }
             E(K::Char('L' | 'l'), M::ALT) => Cmd::DowncaseWord,
             E(K::Char('T' | 't'), M::ALT) => Cmd::TransposeWords(n),
-            // TODO ESC-R (r): Undo all changes made to this line.
+            E(K::Char('R' | 'r'), M::ALT) => Cmd::RevertLine,
             E(K::Char('U' | 'u'), M::ALT) => Cmd::UpcaseWord,
             E(K::Char('Y' | 'y'), M::ALT) => Cmd::YankPop,
+            E(K::Char('.' | '_'), M::ALT) => Cmd::YankLastArg(arg),
+            E(K::Char('/'), M::ALT) => Cmd::DabbrevExpand,
+            E(K::Char('Y'), M::CTRL_ALT) => Cmd::YankNthArg(arg.unwrap_or(1)),
             _ => self.common(rdr, wrt, evt, key, n, positive)?,
         };
         debug!(target: "rustyline", "Emacs command: {:?}", cmd);
@@ -813,7 +854,7 @@ impl<'b> InputState<'b> {
                 Cmd::Replace(Movement::WholeLine, None)
             }
             E(K::Char('u'), M::NONE) => Cmd::Undo(n),
-            // E(K::Char('U'), M::NONE) => Cmd::???, // revert-line
+            E(K::Char('U'), M::NONE) => Cmd::RevertLine,
             E(K::Char('w'), M::NONE) => Cmd::Move(Movement::ForwardWord(n, At::Start, Word::Vi)), /* vi-next-word */
             E(K::Char('W'), M::NONE) => Cmd::Move(Movement::ForwardWord(n, At::Start, Word::Big)), /* vi-next-word */
             // TODO move backward if eol
@@ -1019,6 +1060,31 @@ impl<'b> InputState<'b> {
             } else {
                 Movement::ForwardChar(n)
             }),
+            E(K::Home, M::SHIFT) => Cmd::Select(Movement::BeginningOfLine),
+            E(K::End, M::SHIFT) => Cmd::Select(Movement::EndOfLine),
+            E(K::Left, M::SHIFT) => Cmd::Select(if positive {
+                Movement::BackwardChar(n)
+            } else {
+                Movement::ForwardChar(n)
+            }),
+            E(K::Right, M::SHIFT) => Cmd::Select(if positive {
+                Movement::ForwardChar(n)
+            } else {
+                Movement::BackwardChar(n)
+            }),
+            E(K::Left, M::CTRL_SHIFT) => Cmd::Select(if positive {
+                Movement::BackwardWord(n, Word::Emacs)
+            } else {
+                Movement::ForwardWord(n, At::AfterEnd, Word::Emacs)
+            }),
+            E(K::Right, M::CTRL_SHIFT) => Cmd::Select(if positive {
+                Movement::ForwardWord(n, At::AfterEnd, Word::Emacs)
+            } else {
+                Movement::BackwardWord(n, Word::Emacs)
+            }),
+            E(K::Char('C'), M::CTRL_SHIFT) => Cmd::CopySelection,
+            E(K::Char('X'), M::CTRL_SHIFT) => Cmd::CutSelection,
+            E(K::Char('V'), M::CTRL_SHIFT) if self.enable_selection => Cmd::Yank(n, Anchor::Before),
             #[cfg(any(windows, test))]
             E(K::Char('C'), M::CTRL) => Cmd::Interrupt,
             E(K::Char('D'), M::CTRL) => {
@@ -20,11 +20,14 @@
 
 #[cfg(feature = "custom-bindings")]
 mod binding;
+#[cfg(feature = "with-clap")]
+pub mod clap_helper;
 mod command;
 pub mod completion;
 pub mod config;
 mod edit;
 pub mod error;
+pub mod fuzzy;
 pub mod highlight;
 pub mod hint;
 pub mod history;
@@ -37,20 +40,26 @@ mod tty;
 mod undo;
 pub mod validate;
 
+use std::borrow::Cow;
+use std::cmp;
 use std::fmt;
 use std::io::{self, BufRead, Write};
 use std::path::Path;
 use std::result;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use log::debug;
 use unicode_width::UnicodeWidthStr;
 
-use crate::tty::{RawMode, Renderer, Term, Terminal};
+use crate::tty::{RawMode, RawReader, Renderer, Term, Terminal};
 
 #[cfg(feature = "custom-bindings")]
 pub use crate::binding::{ConditionalEventHandler, Event, EventContext, EventHandler};
-use crate::completion::{longest_common_prefix, Candidate, Completer};
+use crate::completion::{
+    longest_common_prefix, longest_common_prefix_ignore_case, normalize, Candidate, Completer,
+    Completion, PendingCompletion,
+};
 pub use crate::config::{Behavior, ColorMode, CompletionType, Config, EditMode, HistoryDuplicates};
 use crate::edit::State;
 use crate::highlight::Highlighter;
@@ -60,12 +69,44 @@ pub use crate::keymap::{Anchor, At, CharSearch, Cmd, InputMode, Movement, Repeat
 use crate::keymap::{Bindings, InputState, Refresher};
 pub use crate::keys::{KeyCode, KeyEvent, Modifiers};
 use crate::kill_ring::KillRing;
+use crate::layout::{Layout, Position};
 pub use crate::tty::ExternalPrinter;
 use crate::validate::Validator;
 
 /// The error type for I/O and Linux Syscalls (Errno)
 pub type Result<T> = result::Result<T, error::ReadlineError>;
 
+/// Waits for a completion computed in the background, displaying a spinner.
+/// A key press (except `Complete`) cancels the completion: the matching
+/// command is returned (`None` for `Abort`).
+fn wait_completion<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    pending: &PendingCompletion<H::Candidate>,
+) -> Result<result::Result<Completion<H::Candidate>, Option<Cmd>>> {
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+    const TICK: Duration = Duration::from_millis(50);
+    let mut ticks = 0;
+    loop {
+        if let Some(completion) = pending.wait(TICK) {
+            if ticks > 0 {
+                s.refresh_line()?; // erase spinner
+            }
+            return completion.map(Ok);
+        }
+        if rdr.poll_input(0)? {
+            let cmd = s.next_cmd(input_state, rdr, true, true)?;
+            if cmd != Cmd::Complete {
+                s.refresh_line()?;
+                return Ok(Err(if cmd == Cmd::Abort { None } else { Some(cmd) }));
+            }
+        }
+        s.refresh_line_with_msg(Some(&format!(" {}", SPINNER[ticks % SPINNER.len()])))?;
+        ticks += 1;
+    }
+}
+
 /// Completes the line/word
 fn complete_line<H: Helper>(
     rdr: &mut <Terminal as Term>::Reader,
@@ -78,14 +119,60 @@ fn complete_line<H: Helper>(
         unbounded, Skim, SkimItem, SkimItemReceiver, SkimItemSender, SkimOptionsBuilder,
     };
 
+    // maximum number of completions triggered again after inserting the only
+    // candidate
+    const MAX_RETRIGGERS: usize = 16;
+
     let completer = s.helper.unwrap();
+    let mut retriggers = 0;
     // get a list of completions
-    let (start, candidates) = completer.complete(&s.line, s.line.pos(), &s.ctx)?;
+    let completion = loop {
+        let completion = match completer.complete_in_background(&s.line, s.line.pos(), &s.ctx) {
+            Some(pending) => match wait_completion(rdr, s, input_state, &pending)? {
+                Ok(completion) => completion,
+                // cancelled by a key press
+                Err(cmd) => return Ok(cmd),
+            },
+            None => completer.completion(&s.line, s.line.pos(), &s.ctx)?,
+        };
+        if completion.candidates.len() != 1
+            || !completion.retrigger()
+            || retriggers == MAX_RETRIGGERS
+        {
+            break completion;
+        }
+        // insert the only candidate (like a directory) and complete again
+        let backup = s.line.as_str().to_owned();
+        completer.update_candidate(
+            &mut s.line,
+            completion.start,
+            &completion.candidates[0],
+            &completion.unique_replacement(),
+        );
+        s.refresh_line()?;
+        if s.line.as_str() == backup {
+            return Ok(None);
+        }
+        retriggers += 1;
+    };
+    let start = completion.start;
+    let candidates = &completion.candidates;
+    let completion_type = completion
+        .completion_type()
+        .unwrap_or_else(|| config.completion_type());
     // if no completions, we are done
     if candidates.is_empty() {
         s.out.beep()?;
         Ok(None)
-    } else if CompletionType::Circular == config.completion_type() {
+    } else if CompletionType::Circular == completion_type
+        && config.completion_preview()
+        && s.line.pos() == s.line.len()
+        && candidates
+            .iter()
+            .all(|c| c.replacement().starts_with(&s.line[start..]))
+    {
+        preview_complete(rdr, s, input_state, &completion)
+    } else if CompletionType::Circular == completion_type {
         let mark = s.changes.borrow_mut().begin();
         // Save the current edited line before overwriting it
         let backup = s.line.as_str().to_owned();
@@ -95,14 +182,18 @@ fn complete_line<H: Helper>(
         loop {
             // Show completion or original buffer
             if i < candidates.len() {
-                let candidate = candidates[i].replacement();
+                let candidate = if candidates.len() == 1 {
+                    completion.unique_replacement()
+                } else {
+                    Cow::Borrowed(candidates[i].replacement())
+                };
                 // TODO we can't highlight the line buffer directly
                 /*let candidate = if let Some(highlighter) = s.highlighter {
                     highlighter.highlight_candidate(candidate, CompletionType::Circular)
                 } else {
                     Borrowed(candidate)
                 };*/
-                completer.update(&mut s.line, start, candidate);
+                completer.update_candidate(&mut s.line, start, &candidates[i], &candidate);
             } else {
                 // Restore current edited line
                 s.line.update(&backup, backup_pos);
@@ -141,11 +232,19 @@ fn complete_line<H: Helper>(
             }
         }
         Ok(Some(cmd))
-    } else if CompletionType::List == config.completion_type() {
-        if let Some(lcp) = longest_common_prefix(&candidates) {
+    } else if CompletionType::List == completion_type {
+        let lcp = if candidates.len() == 1 {
+            Some(completion.unique_replacement())
+        } else if config.completion_ignore_case() {
+            longest_common_prefix_ignore_case(candidates, config.completion_map_case())
+                .map(Cow::Borrowed)
+        } else {
+            longest_common_prefix(candidates).map(Cow::Borrowed)
+        };
+        if let Some(lcp) = lcp {
             // if we can extend the item, extend it
             if lcp.len() > s.line.pos() - start {
-                completer.update(&mut s.line, start, lcp);
+                completer.update_candidate(&mut s.line, start, &candidates[0], &lcp);
                 s.refresh_line()?;
             }
         }
@@ -167,7 +266,12 @@ fn complete_line<H: Helper>(
         s.line.set_pos(save_pos);
         // we got a second tab, maybe show list of possible completions
         let show_completions = if candidates.len() > config.completion_prompt_limit() {
-            let msg = format!("\nDisplay all {} possibilities? (y or n)", candidates.len());
+            let msg = format!(
+                "\n{}",
+                config
+                    .completion_prompt()
+                    .replace("{}", &candidates.len().to_string())
+            );
             s.out.write_and_flush(msg.as_str())?;
             s.layout.end.row += 1;
             while cmd != Cmd::SelfInsert(1, 'y')
@@ -183,18 +287,29 @@ fn complete_line<H: Helper>(
             true
         };
         if show_completions {
-            page_completions(rdr, s, input_state, &candidates)
+            page_completions(rdr, s, input_state, candidates, config)
         } else {
             s.refresh_line()?;
             Ok(None)
         }
+    } else if CompletionType::Menu == completion_type {
+        if candidates.len() == 1 {
+            completer.update_candidate(
+                &mut s.line,
+                start,
+                &candidates[0],
+                &completion.unique_replacement(),
+            );
+            s.refresh_line()?;
+            return Ok(None);
+        }
+        menu_complete(rdr, s, input_state, start, candidates)
     } else {
         // if fuzzy feature is enabled and on unix based systems check for the
         // corresponding completion_type
         #[cfg(all(unix, feature = "with-fuzzy"))]
         {
-            use std::borrow::Cow;
-            if CompletionType::Fuzzy == config.completion_type() {
+            if CompletionType::Fuzzy == completion_type {
                 struct Candidate {
                     index: usize,
                     text: String,
@@ -241,7 +356,12 @@ fn complete_line<H: Helper>(
                         .downcast_ref::<Candidate>() // downcast to concrete type
                         .expect("something wrong with downcast");
                     if let Some(candidate) = candidates.get(item.index) {
-                        completer.update(&mut s.line, start, candidate.replacement());
+                        completer.update_candidate(
+                            &mut s.line,
+                            start,
+                            candidate,
+                            candidate.replacement(),
+                        );
                     }
                 }
                 s.refresh_line()?;
@@ -251,6 +371,418 @@ fn complete_line<H: Helper>(
     }
 }
 
+/// Circular completion with the selected candidate displayed as ghost text
+/// after the cursor: the line is updated only once the candidate is accepted.
+fn preview_complete<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    completion: &Completion<H::Candidate>,
+) -> Result<Option<Cmd>> {
+    let completer = s.helper.unwrap();
+    let start = completion.start;
+    let typed = s.line.pos() - start;
+    let replacements: Vec<Cow<'_, str>> = if completion.candidates.len() == 1 {
+        vec![completion.unique_replacement()]
+    } else {
+        completion
+            .candidates
+            .iter()
+            .map(|c| Cow::Borrowed(c.replacement()))
+            .collect()
+    };
+    let mut i = 0;
+    loop {
+        // Show completion or original buffer
+        let ghost = replacements.get(i).map(|r| &r[typed..]);
+        s.refresh_line_with_msg(ghost)?;
+
+        let cmd = s.next_cmd(input_state, rdr, true, true)?;
+        match cmd {
+            Cmd::Complete => {
+                i = (i + 1) % (replacements.len() + 1); // Circular
+                if i == replacements.len() {
+                    s.out.beep()?;
+                }
+            }
+            Cmd::CompleteBackward => {
+                if i == 0 {
+                    i = replacements.len(); // Circular
+                    s.out.beep()?;
+                } else {
+                    i -= 1;
+                }
+            }
+            Cmd::Abort => {
+                s.refresh_line()?;
+                return Ok(None);
+            }
+            _ => {
+                if let Some(replacement) = replacements.get(i) {
+                    let candidate = &completion.candidates[i];
+                    completer.update_candidate(&mut s.line, start, candidate, replacement);
+                }
+                s.refresh_line()?;
+                return Ok(Some(cmd));
+            }
+        }
+    }
+}
+
+/// Menu completion: candidates are displayed under the input line and the
+/// selected one is previewed in the line buffer.
+fn menu_complete<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    start: usize,
+    candidates: &[H::Candidate],
+) -> Result<Option<Cmd>> {
+    let completer = s.helper.unwrap();
+    let mark = s.changes.borrow_mut().begin();
+    // Save the current edited line before overwriting it
+    let backup = s.line.as_str().to_owned();
+    let backup_pos = s.line.pos();
+    let mut menu = Menu::new(candidates.len());
+    loop {
+        let candidate = &candidates[menu.selected];
+        completer.update_candidate(&mut s.line, start, candidate, candidate.replacement());
+        let rows = s.out.get_rows().saturating_sub(s.line_rows());
+        let text = menu.render(s, candidates, rows);
+        s.refresh_line_with_msg(Some(&text))?;
+
+        let cmd = match input_state.next_cmd(rdr, s, true, true) {
+            Err(_) if s.out.sigwinch() => {
+                // redraw in place with the new terminal size
+                s.out.update_size();
+                s.update_prompt_size();
+                continue;
+            }
+            cmd => cmd?,
+        };
+        // in vi insert mode, Escape switches to command mode and moves the cursor
+        let navigate = input_state.input_mode != InputMode::Command;
+        match cmd {
+            Cmd::Complete | Cmd::Move(Movement::ForwardChar(_)) if navigate => menu.next(),
+            Cmd::CompleteBackward | Cmd::Move(Movement::BackwardChar(_)) if navigate => {
+                menu.previous();
+            }
+            Cmd::LineDownOrNextHistory(_) | Cmd::NextHistory if navigate => menu.down(),
+            Cmd::LineUpOrPreviousHistory(_) | Cmd::PreviousHistory if navigate => menu.up(),
+            Cmd::Abort => {
+                // Re-show original buffer
+                s.line.update(&backup, backup_pos);
+                s.refresh_line()?;
+                s.changes.borrow_mut().truncate(mark);
+                return Ok(None);
+            }
+            Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. } => {
+                // accept the selected candidate only
+                s.changes.borrow_mut().end();
+                s.refresh_line()?;
+                return Ok(None);
+            }
+            _ => {
+                s.changes.borrow_mut().end();
+                s.refresh_line()?;
+                return Ok(Some(cmd));
+            }
+        }
+    }
+}
+
+/// Autocomplete-as-you-type: once the user stops typing, displays the
+/// candidates below the line, without modifying it.
+/// Return the command which has closed the candidate list, if any.
+fn auto_complete<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    config: &Config,
+) -> Result<Option<Cmd>> {
+    const TICK: Duration = Duration::from_millis(50);
+    let delay = config.auto_complete_delay();
+    // still typing
+    if delay > 0 && rdr.poll_input(i32::from(delay))? {
+        return Ok(None);
+    }
+    let completer = s.helper.unwrap();
+    // only once the user has stopped typing, cancelled by the next key press
+    let pending = completer.complete_in_background(&s.line, s.line.pos(), &s.ctx);
+    let Completion {
+        start, candidates, ..
+    } = match pending {
+        Some(pending) => loop {
+            if let Some(completion) = pending.wait(TICK) {
+                break completion?;
+            }
+            if rdr.poll_input(0)? {
+                return Ok(None);
+            }
+        },
+        None => completer.completion(&s.line, s.line.pos(), &s.ctx)?,
+    };
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    let mut menu = Menu::vertical(candidates.len());
+    loop {
+        let rows = s.out.get_rows().saturating_sub(s.line_rows());
+        let max_rows = cmp::min(rows, config.auto_complete_rows());
+        let text = menu.render(s, &candidates, max_rows);
+        s.refresh_line_with_msg(Some(&text))?;
+
+        let cmd = match input_state.next_cmd(rdr, s, true, true) {
+            Err(_) if s.out.sigwinch() => {
+                // redraw in place with the new terminal size
+                s.out.update_size();
+                s.update_prompt_size();
+                continue;
+            }
+            cmd => cmd?,
+        };
+        match cmd {
+            // until the list is focused, Up and Down keep their usual meaning
+            Cmd::LineDownOrNextHistory(_) | Cmd::NextHistory if menu.focused => menu.down(),
+            Cmd::LineUpOrPreviousHistory(_) | Cmd::PreviousHistory if menu.focused => menu.up(),
+            Cmd::Complete if !menu.focused => menu.focused = true,
+            Cmd::Complete => {
+                s.changes.borrow_mut().begin();
+                let candidate = &candidates[menu.selected];
+                completer.update_candidate(&mut s.line, start, candidate, candidate.replacement());
+                s.changes.borrow_mut().end();
+                s.refresh_line()?;
+                return Ok(None);
+            }
+            Cmd::Abort => {
+                s.refresh_line()?;
+                return Ok(None);
+            }
+            _ => {
+                // hide the candidates, they are updated if the line is modified
+                s.refresh_line()?;
+                return Ok(Some(cmd));
+            }
+        }
+    }
+}
+
+/// Grid of candidates (row-major) with the selected one highlighted.
+struct Menu {
+    len: usize,       // number of candidates
+    max_cols: usize,  // maximum number of columns
+    cols: usize,      // number of columns of the last rendering
+    selected: usize,  // index of the selected candidate
+    first_row: usize, // first visible row
+    focused: bool,    // the selected candidate is highlighted
+}
+
+impl Menu {
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            max_cols: usize::MAX,
+            cols: 1,
+            selected: 0,
+            first_row: 0,
+            focused: true,
+        }
+    }
+
+    /// One candidate per row, not focused
+    fn vertical(len: usize) -> Self {
+        Self {
+            max_cols: 1,
+            focused: false,
+            ..Self::new(len)
+        }
+    }
+
+    fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.len;
+    }
+
+    fn previous(&mut self) {
+        self.selected = (self.selected + self.len - 1) % self.len;
+    }
+
+    fn down(&mut self) {
+        if self.selected + self.cols < self.len {
+            self.selected += self.cols;
+        } else {
+            // wrap around to the first row
+            self.selected %= self.cols;
+        }
+    }
+
+    fn up(&mut self) {
+        if self.selected >= self.cols {
+            self.selected -= self.cols;
+        } else {
+            // wrap around to the last row
+            let last_row = (self.len - 1) / self.cols;
+            let i = last_row * self.cols + self.selected;
+            self.selected = if i < self.len { i } else { i - self.cols };
+        }
+    }
+
+    /// Renders the visible rows of the menu (at most `max_rows`), each one
+    /// preceded by a newline.
+    fn render<C: Candidate, H: Helper>(
+        &mut self,
+        s: &State<'_, '_, H>,
+        candidates: &[C],
+        max_rows: usize,
+    ) -> String {
+        let columns = Columns::new(candidates, s.out.get_columns());
+        self.cols = cmp::min(columns.num_cols, self.max_cols);
+        let num_rows = (self.len - 1) / self.cols + 1;
+        let max_rows = cmp::max(1, max_rows);
+        // scroll to keep the selected candidate visible
+        let row = self.selected / self.cols;
+        if row < self.first_row {
+            self.first_row = row;
+        } else if row >= self.first_row + max_rows {
+            self.first_row = row + 1 - max_rows;
+        }
+        self.first_row = cmp::min(self.first_row, num_rows.saturating_sub(max_rows));
+
+        let highlighter = s.highlighter();
+        let mut ab = String::new();
+        for row in self.first_row..cmp::min(num_rows, self.first_row + max_rows) {
+            ab.push('\n');
+            for col in 0..self.cols {
+                let i = row * self.cols + col;
+                if i >= self.len {
+                    break;
+                }
+                let candidate = candidates[i].display();
+                let mut width = candidate.width();
+                if i != self.selected || !self.focused {
+                    if let Some(highlighter) = highlighter {
+                        ab.push_str(
+                            &highlighter.highlight_candidate(candidate, CompletionType::Menu),
+                        );
+                    } else {
+                        ab.push_str(candidate);
+                    }
+                } else if s.out.colors_enabled() {
+                    // reverse video
+                    ab.push_str("\x1b[7m");
+                    ab.push_str(candidate);
+                    ab.push_str("\x1b[27m");
+                } else {
+                    ab.push('[');
+                    ab.push_str(candidate);
+                    ab.push(']');
+                    width += 2;
+                }
+                width = columns.push_description(&mut ab, &candidates[i], width);
+                if col + 1 < self.cols && i + 1 < self.len {
+                    columns.pad(&mut ab, width);
+                }
+            }
+        }
+        ab
+    }
+}
+
+/// Layout of the candidates in columns, with their descriptions if any.
+struct Columns {
+    display_width: usize,     // max display width
+    description_width: usize, // max description width (maybe truncated)
+    width: usize,             // column width, including padding
+    num_cols: usize,
+}
+
+impl Columns {
+    const MIN_COL_PAD: usize = 2;
+    const DESCRIPTION_SEP: &'static str = "  ";
+
+    fn new<C: Candidate>(candidates: &[C], term_cols: usize) -> Self {
+        let display_width = candidates
+            .iter()
+            .map(|c| c.display().width())
+            .max()
+            .unwrap_or(0);
+        let mut description_width = candidates
+            .iter()
+            .filter_map(|c| c.description().map(UnicodeWidthStr::width))
+            .max()
+            .unwrap_or(0);
+        let mut width = display_width + Self::MIN_COL_PAD;
+        if description_width > 0 {
+            width += Self::DESCRIPTION_SEP.len() + description_width;
+        }
+        if width > term_cols {
+            // truncate descriptions to fit
+            description_width = description_width.saturating_sub(width - term_cols);
+            width = term_cols;
+        }
+        Self {
+            display_width,
+            description_width,
+            width,
+            num_cols: std::cmp::max(1, term_cols / std::cmp::max(1, width)),
+        }
+    }
+
+    /// Appends the description of `candidate`, aligned with the other ones,
+    /// after its display of `width` columns.
+    /// Returns the width of the cell.
+    fn push_description<C: Candidate>(
+        &self,
+        ab: &mut String,
+        candidate: &C,
+        width: usize,
+    ) -> usize {
+        let description = match candidate.description() {
+            Some(description) if self.description_width > 0 => description,
+            _ => return width,
+        };
+        let mut width = self.pad_to(ab, width, self.display_width);
+        ab.push_str(Self::DESCRIPTION_SEP);
+        width += Self::DESCRIPTION_SEP.len();
+        let description = truncate(description, self.description_width);
+        ab.push_str(&description);
+        width + description.width()
+    }
+
+    /// Appends spaces after a cell of `width` columns.
+    fn pad(&self, ab: &mut String, width: usize) {
+        self.pad_to(ab, width, self.width);
+    }
+
+    fn pad_to(&self, ab: &mut String, width: usize, to: usize) -> usize {
+        for _ in width..to {
+            ab.push(' ');
+        }
+        std::cmp::max(width, to)
+    }
+}
+
+/// Truncates `s` to `width` columns, with an ellipsis if needed.
+fn truncate(s: &str, width: usize) -> std::borrow::Cow<'_, str> {
+    use unicode_width::UnicodeWidthChar;
+    if s.width() <= width {
+        return std::borrow::Cow::Borrowed(s);
+    }
+    let mut truncated = String::with_capacity(s.len());
+    let mut w = 0;
+    for c in s.chars() {
+        let cw = c.width().unwrap_or(0);
+        if w + cw + 1 > width {
+            break;
+        }
+        truncated.push(c);
+        w += cw;
+    }
+    if width > 0 {
+        truncated.push('…');
+    }
+    std::borrow::Cow::Owned(truncated)
+}
+
 /// Completes the current hint
 fn complete_hint_line<H: Helper>(s: &mut State<'_, '_, H>) -> Result<()> {
     let hint = match s.hint.as_ref() {
@@ -273,79 +805,175 @@ fn page_completions<C: Candidate, H: Helper>(
     s: &mut State<'_, '_, H>,
     input_state: &mut InputState,
     candidates: &[C],
+    config: &Config,
 ) -> Result<Option<Cmd>> {
-    use std::cmp;
-
-    let min_col_pad = 2;
-    let cols = s.out.get_columns();
-    let max_width = cmp::min(
-        cols,
-        candidates
-            .iter()
-            .map(|s| s.display().width())
-            .max()
-            .unwrap()
-            + min_col_pad,
-    );
-    let num_cols = cols / max_width;
-
-    let mut pause_row = s.out.get_rows() - 1;
-    let num_rows = (candidates.len() + num_cols - 1) / num_cols;
-    let mut ab = String::new();
-    for row in 0..num_rows {
-        if row == pause_row {
-            s.out.write_and_flush("\n--More--")?;
-            let mut cmd = Cmd::Noop;
-            while cmd != Cmd::SelfInsert(1, 'y')
-                && cmd != Cmd::SelfInsert(1, 'Y')
-                && cmd != Cmd::SelfInsert(1, 'n')
-                && cmd != Cmd::SelfInsert(1, 'N')
-                && cmd != Cmd::SelfInsert(1, 'q')
-                && cmd != Cmd::SelfInsert(1, 'Q')
-                && cmd != Cmd::SelfInsert(1, ' ')
-                && cmd != Cmd::Kill(Movement::BackwardChar(1))
-                && cmd != Cmd::AcceptLine
-                && cmd != Cmd::Newline
-                && !matches!(cmd, Cmd::AcceptOrInsertLine { .. })
-            {
-                cmd = s.next_cmd(input_state, rdr, false, true)?;
-            }
-            match cmd {
-                Cmd::SelfInsert(1, 'y' | 'Y' | ' ') => {
-                    pause_row += s.out.get_rows() - 1;
-                }
-                Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. } => {
-                    pause_row += 1;
+    let columns = Columns::new(candidates, s.out.get_columns());
+    let page = cmp::max(1, s.out.get_rows() - 1);
+    let mut rows = candidate_rows(s, &columns, candidates, None);
+    // rows[top..bottom] are on screen
+    let mut top: usize = 0;
+    let mut bottom = cmp::min(page, rows.len());
+    for row in &rows[..bottom] {
+        s.out.write_and_flush("\n")?;
+        s.out.write_and_flush(row)?;
+    }
+    while bottom < rows.len() {
+        s.out
+            .write_and_flush(&format!("\n{}", config.completion_more_prompt()))?;
+        let mut cmd = Cmd::Noop;
+        while !matches!(
+            cmd,
+            Cmd::SelfInsert(1, 'y' | 'Y' | 'n' | 'N' | 'q' | 'Q' | ' ' | 'b' | 'B' | '/')
+                | Cmd::Kill(Movement::BackwardChar(1))
+                | Cmd::AcceptLine
+                | Cmd::Newline
+                | Cmd::AcceptOrInsertLine { .. }
+                | Cmd::LineDownOrNextHistory(_)
+                | Cmd::LineUpOrPreviousHistory(_)
+                | Cmd::Abort
+        ) {
+            cmd = s.next_cmd(input_state, rdr, false, true)?;
+        }
+        let (next_top, next_bottom) = match cmd {
+            Cmd::SelfInsert(1, 'y' | 'Y' | ' ') => (top, cmp::min(bottom + page, rows.len())),
+            Cmd::AcceptLine
+            | Cmd::Newline
+            | Cmd::AcceptOrInsertLine { .. }
+            | Cmd::LineDownOrNextHistory(_) => (top, bottom + 1),
+            Cmd::SelfInsert(1, 'b' | 'B') => (top.saturating_sub(page), bottom),
+            Cmd::LineUpOrPreviousHistory(_) => (top.saturating_sub(1), bottom),
+            Cmd::SelfInsert(1, '/') => {
+                if let Some(filtered) = filter_candidates(rdr, s, input_state, candidates, config)?
+                {
+                    rows = candidate_rows(s, &columns, candidates, Some(&filtered));
+                    (0, usize::MAX)
+                } else {
+                    (top, usize::MAX)
                 }
-                _ => break,
             }
+            _ => break,
+        };
+        // rows on screen above the prompt
+        let mut shown = bottom - top;
+        if next_bottom == bottom {
+            // backward: redraw the previous rows
+            if next_top == top {
+                s.out.beep()?;
+                continue;
+            }
+            top = next_top;
+        } else if next_bottom == usize::MAX {
+            // filtered: redraw from the top, the pattern being on the last row
+            shown += 1;
+            top = next_top;
+        } else {
+            for row in &rows[bottom..next_bottom] {
+                s.out.write_and_flush("\n")?;
+                s.out.write_and_flush(row)?;
+            }
+            top = next_bottom.saturating_sub(page);
+            bottom = next_bottom;
+            continue;
         }
-        s.out.write_and_flush("\n")?;
-        ab.clear();
+        bottom = cmp::min(top + page, rows.len());
+        // clear only the rows of the pager, from the prompt up
+        let last = Position { col: 0, row: shown };
+        s.out.clear_rows(&Layout {
+            cursor: last,
+            end: last,
+            ..Layout::default()
+        })?;
+        s.out.write_and_flush(&rows[top..bottom].join("\n"))?;
+    }
+    s.out.write_and_flush("\n")?;
+    s.layout.end.row = 0; // dirty way to make clear_old_rows do nothing
+    s.layout.cursor.row = 0;
+    s.refresh_line()?;
+    Ok(None)
+}
+
+/// Formats the `candidates` (only the `selected` ones if any) in rows of
+/// `columns`.
+fn candidate_rows<C: Candidate, H: Helper>(
+    s: &State<'_, '_, H>,
+    columns: &Columns,
+    candidates: &[C],
+    selected: Option<&[usize]>,
+) -> Vec<String> {
+    let len = selected.map_or(candidates.len(), <[usize]>::len);
+    let num_cols = columns.num_cols;
+    let num_rows = (len + num_cols - 1) / num_cols;
+    let mut rows = Vec::with_capacity(num_rows);
+    for row in 0..num_rows {
+        let mut ab = String::new();
         for col in 0..num_cols {
             let i = (col * num_rows) + row;
-            if i < candidates.len() {
-                let candidate = &candidates[i].display();
-                let width = candidate.width();
+            if i < len {
+                let candidate = &candidates[selected.map_or(i, |selected| selected[i])];
+                let display = candidate.display();
+                let mut width = display.width();
                 if let Some(highlighter) = s.highlighter() {
-                    ab.push_str(&highlighter.highlight_candidate(candidate, CompletionType::List));
+                    ab.push_str(&highlighter.highlight_candidate(display, CompletionType::List));
                 } else {
-                    ab.push_str(candidate);
+                    ab.push_str(display);
                 }
-                if ((col + 1) * num_rows) + row < candidates.len() {
-                    for _ in width..max_width {
-                        ab.push(' ');
-                    }
+                width = columns.push_description(&mut ab, candidate, width);
+                if ((col + 1) * num_rows) + row < len {
+                    columns.pad(&mut ab, width);
                 }
             }
         }
-        s.out.write_and_flush(ab.as_str())?;
+        rows.push(ab);
     }
-    s.out.write_and_flush("\n")?;
-    s.layout.end.row = 0; // dirty way to make clear_old_rows do nothing
-    s.layout.cursor.row = 0;
-    s.refresh_line()?;
-    Ok(None)
+    rows
+}
+
+/// Reads a pattern after a `/` and returns the indexes of the `candidates`
+/// containing it, or `None` if the pattern is cancelled or matches nothing.
+fn filter_candidates<C: Candidate, H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    candidates: &[C],
+    config: &Config,
+) -> Result<Option<Vec<usize>>> {
+    use unicode_width::UnicodeWidthChar;
+    s.out.write_and_flush("\n/")?;
+    let mut pattern = String::new();
+    loop {
+        match s.next_cmd(input_state, rdr, false, true)? {
+            Cmd::SelfInsert(_, c) => {
+                pattern.push(c);
+                s.out.write_and_flush(c.encode_utf8(&mut [0; 4]))?;
+            }
+            Cmd::Kill(Movement::BackwardChar(_)) => {
+                if let Some(c) = pattern.pop() {
+                    let width = c.width().unwrap_or(0);
+                    s.out.write_and_flush(&"\x08".repeat(width))?;
+                    s.out.write_and_flush(&" ".repeat(width))?;
+                    s.out.write_and_flush(&"\x08".repeat(width))?;
+                }
+            }
+            Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. } => break,
+            Cmd::Abort => return Ok(None),
+            _ => {}
+        }
+    }
+    let ignore_case = config.completion_ignore_case();
+    let pattern = normalize(&pattern, ignore_case);
+    let filtered: Vec<usize> = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, candidate)| {
+            normalize(candidate.display(), ignore_case).contains(pattern.as_ref())
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if filtered.is_empty() {
+        s.out.beep()?;
+        return Ok(None);
+    }
+    Ok(Some(filtered))
 }
 
 /// Incremental search
@@ -601,7 +1229,7 @@ impl<H: Helper> Editor<H> {
         );
         Self {
             term,
-            history: History::with_config(config),
+            history: History::with_config(config.clone()),
             helper: None,
             kill_ring: Arc::new(Mutex::new(KillRing::new(60))),
             config,
@@ -676,6 +1304,7 @@ impl<H: Helper> Editor<H> {
 
         let mut input_state = InputState::new(&self.config, &self.custom_bindings);
 
+        s.set_mark_modified_lines(self.config.mark_modified_lines());
         s.line.set_delete_listener(self.kill_ring.clone());
         s.line.set_change_listener(s.changes.clone());
 
@@ -683,6 +1312,7 @@ impl<H: Helper> Editor<H> {
             s.line
                 .update((left.to_owned() + right).as_ref(), left.len());
         }
+        s.set_initial_line();
 
         let mut rdr = self.term.create_reader(&self.config, term_key_map);
         if self.term.is_output_tty() && self.config.check_cursor_position() {
@@ -696,12 +1326,31 @@ impl<H: Helper> Editor<H> {
         }
         s.refresh_line()?;
 
+        // command which has closed the candidates displayed while typing
+        let mut next_cmd = None;
         loop {
-            let mut cmd = s.next_cmd(&mut input_state, &mut rdr, false, false)?;
+            let mut cmd = match next_cmd.take() {
+                Some(cmd) => cmd,
+                None => s.next_cmd(&mut input_state, &mut rdr, false, false)?,
+            };
 
             if cmd.should_reset_kill_ring() {
                 self.reset_kill_ring();
             }
+            if !matches!(cmd, Cmd::YankLastArg(_)) {
+                s.reset_yank_arg();
+            }
+            if cmd != Cmd::DabbrevExpand {
+                s.reset_dabbrev();
+            }
+
+            if matches!(
+                cmd,
+                Cmd::Complete | Cmd::ReverseSearchHistory | Cmd::Suspend | Cmd::QuotedInsert
+            ) && s.reset_selection()
+            {
+                s.refresh_line()?;
+            }
 
             // First trigger commands that need extra input
 
@@ -759,9 +1408,22 @@ impl<H: Helper> Editor<H> {
                 self.term.cursor = s.layout.cursor.col;
             }
 
+            // line before the command, to display the candidates when it's modified
+            let line = if self.config.auto_complete() && s.helper.is_some() {
+                Some(s.line.as_str().to_owned())
+            } else {
+                None
+            };
             // Execute things can be done solely on a state object
             match command::execute(cmd, &mut s, &input_state, &self.kill_ring, &self.config)? {
-                command::Status::Proceed => continue,
+                command::Status::Proceed => {
+                    if matches!(line, Some(ref line) if line != s.line.as_str())
+                        && input_state.input_mode != InputMode::Command
+                    {
+                        next_cmd = auto_complete(&mut rdr, &mut s, &mut input_state, &self.config)?;
+                    }
+                    continue;
+                }
                 command::Status::Submit => break,
             }
         }
@@ -773,7 +1435,16 @@ impl<H: Helper> Editor<H> {
         if cfg!(windows) {
             let _ = original_mode; // silent warning
         }
-        Ok(s.line.into_string())
+        let modified_lines = if self.config.revert_all_at_newline() {
+            Vec::new()
+        } else {
+            s.take_modified_lines()
+        };
+        let line = s.line.into_string();
+        for (index, entry) in modified_lines {
+            self.history.set(index, entry);
+        }
+        Ok(line)
     }
 
     /// Load the history from the specified file.
@@ -1,6 +1,6 @@
 ///! Basic commands tests.
 use super::{assert_cursor, assert_line, assert_line_with_initial, init_editor};
-use crate::config::EditMode;
+use crate::config::{Configurer, EditMode};
 use crate::error::ReadlineError;
 use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
 
@@ -338,3 +338,140 @@ fn ctrl__() {
         }
     }
 }
+
+// `initial`: line status before `keys` pressed: strings before and after cursor
+// `keys`: keys to press with selection enabled
+// `expected_line`: line after enter key
+fn assert_selection(mode: EditMode, initial: (&str, &str), keys: &[E], expected_line: &str) {
+    let mut editor = init_editor(mode, keys);
+    editor.set_selection(true);
+    let actual_line = editor.readline_with_initial(">>", initial).unwrap();
+    assert_eq!(expected_line, actual_line);
+}
+
+#[test]
+fn shift_left_disabled() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_line_with_initial(
+            *mode,
+            ("Hello", ""),
+            &[E(K::Left, M::SHIFT), E(K::Char('X'), M::NONE), E::ENTER],
+            "HelloX",
+        );
+    }
+}
+
+#[test]
+fn shift_left_replace_selection() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_selection(
+            *mode,
+            ("Hello", ""),
+            &[
+                E(K::Left, M::SHIFT),
+                E(K::Left, M::SHIFT),
+                E(K::Char('X'), M::NONE),
+                E::ENTER,
+            ],
+            "HelX",
+        );
+    }
+}
+
+#[test]
+fn shift_home_delete_selection() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_selection(
+            *mode,
+            ("Hello", ", world"),
+            &[E(K::Home, M::SHIFT), E::BACKSPACE, E::ENTER],
+            ", world",
+        );
+    }
+}
+
+#[test]
+fn move_clears_selection() {
+    assert_selection(
+        EditMode::Emacs,
+        ("Hello", ""),
+        &[
+            E(K::Left, M::SHIFT),
+            E(K::Home, M::NONE),
+            E(K::Char('X'), M::NONE),
+            E::ENTER,
+        ],
+        "XHello",
+    );
+}
+
+#[test]
+fn cut_and_paste_selection() {
+    assert_selection(
+        EditMode::Emacs,
+        ("Hello world", ""),
+        &[
+            E(K::Left, M::CTRL_SHIFT),
+            E(K::Char('X'), M::CTRL_SHIFT),
+            E(K::Home, M::NONE),
+            E(K::Char('V'), M::CTRL_SHIFT),
+            E::ENTER,
+        ],
+        "worldHello ",
+    );
+}
+
+#[test]
+fn paste_selection_disabled() {
+    assert_line_with_initial(
+        EditMode::Emacs,
+        ("Hello world", ""),
+        &[E::ctrl('W'), E(K::Char('V'), M::CTRL_SHIFT), E::ENTER],
+        "Hello ",
+    );
+}
+
+#[test]
+fn copy_selection() {
+    assert_selection(
+        EditMode::Emacs,
+        ("Hello", " world"),
+        &[
+            E(K::Home, M::SHIFT),
+            E(K::Char('C'), M::CTRL_SHIFT),
+            E(K::End, M::NONE),
+            E::ctrl('Y'),
+            E::ENTER,
+        ],
+        "Hello worldHello",
+    );
+    // a new kill ring entry, even after a kill
+    assert_selection(
+        EditMode::Emacs,
+        ("Hello", " world"),
+        &[
+            E::alt('D'),
+            E(K::Home, M::SHIFT),
+            E(K::Char('C'), M::CTRL_SHIFT),
+            E(K::End, M::NONE),
+            E::ctrl('Y'),
+            E::ENTER,
+        ],
+        "HelloHello",
+    );
+}
+
+#[test]
+fn undo_replaced_selection() {
+    assert_selection(
+        EditMode::Emacs,
+        ("Hello", ""),
+        &[
+            E(K::Left, M::SHIFT),
+            E(K::Char('X'), M::NONE),
+            E::ctrl('_'),
+            E::ENTER,
+        ],
+        "Hello",
+    );
+}
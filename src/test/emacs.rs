@@ -1,7 +1,11 @@
 //! Emacs specific key bindings
+#[cfg(feature = "custom-bindings")]
+use super::assert_cursor_with_binding;
 use super::{assert_cursor, assert_history};
 use crate::config::EditMode;
 use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+#[cfg(feature = "custom-bindings")]
+use crate::Cmd;
 
 #[test]
 fn ctrl_a() {
@@ -244,6 +248,115 @@ fn ctrl_x_ctrl_u() {
     );
 }
 
+#[test]
+fn ctrl_x_star() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in &["a.rs", "b.rs", "c.txt"] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+    let dir = format!(
+        "{}{}",
+        dir.path().to_str().unwrap(),
+        std::path::MAIN_SEPARATOR
+    );
+    let line = format!("ls {}*.rs", dir);
+    assert_cursor(
+        EditMode::Emacs,
+        (&line, ""),
+        &[E::ctrl('X'), E::from('*'), E::ENTER],
+        (&format!("ls {}a.rs {}b.rs", dir, dir), ""),
+    );
+    // one undo step
+    assert_cursor(
+        EditMode::Emacs,
+        (&line, ""),
+        &[
+            E::ctrl('X'),
+            E::from('*'),
+            E::ctrl('X'),
+            E::ctrl('U'),
+            E::ENTER,
+        ],
+        (&line, ""),
+    );
+}
+
+#[test]
+fn meta_r() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hello, ", "world"),
+        &[E::ctrl('W'), E::from('X'), E::alt('R'), E::ENTER],
+        ("Hello, ", "world"),
+    );
+    // revert-line is undoable
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hello, ", "world"),
+        &[E::ctrl('K'), E::alt('r'), E::ctrl('_'), E::ENTER],
+        ("Hello, ", ""),
+    );
+    // history entry as loaded
+    assert_history(
+        EditMode::Emacs,
+        &["line1"],
+        &[
+            E::from('a'),
+            E::ctrl('P'),
+            E::ctrl('W'),
+            E::alt('r'),
+            E::ENTER,
+        ],
+        "",
+        ("line1", ""),
+    );
+}
+
+#[test]
+#[cfg(feature = "custom-bindings")]
+fn redo() {
+    let binding = (E::alt('/'), Cmd::Redo(1));
+    assert_cursor_with_binding(
+        EditMode::Emacs,
+        binding.clone(),
+        ("Hello, ", "world"),
+        &[E::ctrl('W'), E::ctrl('_'), E::alt('/'), E::ENTER],
+        ("", "world"),
+    );
+    // redo after several undos, with a numeric argument
+    assert_cursor_with_binding(
+        EditMode::Emacs,
+        binding.clone(),
+        ("", ""),
+        &[
+            E::from('a'),
+            E::from(' '),
+            E::from('b'),
+            E::ctrl('_'),
+            E::ctrl('_'),
+            E::ctrl('_'),
+            E::alt('2'),
+            E::alt('/'),
+            E::ENTER,
+        ],
+        ("a b", ""),
+    );
+    // new edit invalidates redo
+    assert_cursor_with_binding(
+        EditMode::Emacs,
+        binding,
+        ("Hello", ""),
+        &[
+            E::ctrl('W'),
+            E::ctrl('_'),
+            E::from('!'),
+            E::alt('/'),
+            E::ENTER,
+        ],
+        ("Hello!", ""),
+    );
+}
+
 #[test]
 fn meta_b() {
     assert_cursor(
@@ -403,6 +516,115 @@ fn meta_y() {
     );
 }
 
+#[test]
+fn meta_dot() {
+    let entries = &["ls /etc", "cat 'a b' foo.txt"];
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::from('v'), E::from(' '), E::alt('.'), E::ENTER],
+        "",
+        ("v foo.txt", ""),
+    );
+    // repeated: previous history entries
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('.'), E::alt('.'), E::ENTER],
+        "",
+        ("/etc", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('.'), E::alt('.'), E::alt('.'), E::ENTER],
+        "",
+        ("/etc", ""),
+    );
+    // with numeric argument
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('1'), E::alt('_'), E::ENTER],
+        "",
+        ("'a b'", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('0'), E::alt('.'), E::alt('.'), E::ENTER],
+        "",
+        ("ls", ""),
+    );
+}
+
+#[test]
+fn meta_slash() {
+    let entries = &["cargo build", "cargo test --workspace", "ls"];
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::from('c'), E::alt('/'), E::ENTER],
+        "",
+        ("cargo", ""),
+    );
+    // most recent first
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::from('-'), E::alt('/'), E::ENTER],
+        "",
+        ("--workspace", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::from('t'), E::alt('/'), E::alt('/'), E::ENTER],
+        "",
+        ("t", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[
+            E::from('b'),
+            E::alt('/'),
+            E::from(' '),
+            E::from('t'),
+            E::alt('/'),
+            E::ENTER,
+        ],
+        "",
+        ("build test", ""),
+    );
+}
+
+#[test]
+fn meta_ctrl_y() {
+    let entries = &["cat 'a b' foo.txt"];
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E(K::Char('Y'), M::CTRL_ALT), E::ENTER],
+        "",
+        ("'a b'", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('-'), E(K::Char('Y'), M::CTRL_ALT), E::ENTER],
+        "",
+        ("foo.txt", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('5'), E(K::Char('Y'), M::CTRL_ALT), E::ENTER],
+        "",
+        ("", ""),
+    );
+}
+
 #[test]
 fn meta_backspace() {
     assert_cursor(
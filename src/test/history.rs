@@ -1,5 +1,5 @@
 //! History related commands tests
-use super::assert_history;
+use super::{assert_history, init_editor};
 use crate::config::EditMode;
 use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
 
@@ -241,3 +241,58 @@ fn meta_gt() {
         ("a", ""),
     );
 }
+
+#[test]
+fn modified_entry() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        // edits are kept while browsing history
+        assert_history(
+            *mode,
+            &["line1", "line2"],
+            &[
+                E(K::Up, M::NONE),
+                E::from('X'),
+                E(K::Up, M::NONE),
+                E(K::Down, M::NONE),
+                E::ENTER,
+            ],
+            "",
+            ("line2X", ""),
+        );
+        // with their own undo stack
+        assert_history(
+            *mode,
+            &["line1", "line2"],
+            &[
+                E(K::Up, M::NONE),
+                E::from('X'),
+                E(K::Up, M::NONE),
+                E::from('Y'),
+                E(K::Down, M::NONE),
+                E::ctrl('_'),
+                E(K::Up, M::NONE),
+                E::ENTER,
+            ],
+            "",
+            ("line1Y", ""),
+        );
+    }
+}
+
+#[test]
+fn revert_all_at_newline() {
+    use crate::config::Configurer;
+
+    let keys = [E(K::Up, M::NONE), E::from('X'), E(K::Up, M::NONE), E::ENTER];
+    for revert in &[true, false] {
+        let mut editor = init_editor(EditMode::Emacs, &keys);
+        editor.set_revert_all_at_newline(*revert);
+        editor.history.add("line1");
+        editor.history.add("line2");
+        let actual_line = editor.readline("").unwrap();
+        assert_eq!("line1", actual_line);
+        let expected = if *revert { "line2" } else { "line2X" };
+        assert_eq!(Some(expected), editor.history.get(1).map(String::as_str));
+        assert_eq!(Some("line1"), editor.history.get(0).map(String::as_str));
+    }
+}
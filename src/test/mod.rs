@@ -1,7 +1,7 @@
 use std::vec::IntoIter;
 
-use crate::completion::Completer;
-use crate::config::{Config, EditMode};
+use crate::completion::{Completer, Completion};
+use crate::config::{CompletionType, Config, EditMode};
 use crate::edit::init_state;
 use crate::highlight::Highlighter;
 use crate::hint::Hinter;
@@ -71,6 +71,382 @@ fn complete_line() {
     assert_eq!(4, s.line.pos());
 }
 
+#[test]
+fn complete_line_preview() {
+    let config = Config::builder().completion_preview(true).build();
+    let bindings = Bindings::new();
+    for (keys, line) in [
+        // cycle back to the original line, then to the candidate
+        (vec![E::from('\t'), E::from('\t'), E::ENTER], "rust"),
+        (vec![E::ESC], "rus"),
+    ] {
+        let mut out = Sink::default();
+        let history = crate::history::History::new();
+        let helper = Some(SimpleCompleter);
+        let mut s = init_state(&mut out, "rus", 3, helper.as_ref(), &history);
+        let mut input_state = InputState::new(&config, &bindings);
+        let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+        super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+        assert_eq!(line, s.line.as_str());
+    }
+
+    // the accepted candidate is a single change
+    let mut editor = Editor::with_config(config);
+    editor.set_helper(Some(SimpleCompleter));
+    editor.term.keys.extend([
+        E::from('\t'),
+        E::from('x'),
+        E(K::Char('_'), M::CTRL),
+        E(K::Char('_'), M::CTRL),
+        E::ENTER,
+    ]);
+    assert_eq!(
+        "rus",
+        editor.readline_with_initial("", ("rus", "")).unwrap()
+    );
+}
+
+struct ReadmeCompleter;
+impl Completer for ReadmeCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<String>)> {
+        Ok((0, vec!["readme".to_owned(), "README.md".to_owned()]))
+    }
+}
+impl Hinter for ReadmeCompleter {
+    type Hint = String;
+}
+impl Helper for ReadmeCompleter {}
+impl Highlighter for ReadmeCompleter {}
+impl Validator for ReadmeCompleter {}
+
+#[test]
+fn complete_line_ignore_case() {
+    let bindings = Bindings::new();
+    for (ignore_case, line) in [(false, "Read"), (true, "readme")] {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .completion_ignore_case(ignore_case)
+            .build();
+        let mut out = Sink::default();
+        let history = crate::history::History::new();
+        let helper = Some(ReadmeCompleter);
+        let mut s = init_state(&mut out, "Read", 4, helper.as_ref(), &history);
+        let mut input_state = InputState::new(&config, &bindings);
+        let mut rdr: IntoIter<KeyEvent> = vec![E::ENTER].into_iter();
+        super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+        assert_eq!(line, s.line.as_str());
+    }
+}
+
+struct DirCompleter;
+impl Completer for DirCompleter {
+    type Candidate = String;
+
+    fn completion(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Result<Completion<String>> {
+        Ok(match &line[..pos] {
+            "d" => Completion::new(0, vec!["dir/".to_owned()]).with_retrigger(true),
+            "dir/" => Completion::new(0, vec!["dir/file".to_owned()]).with_suffix(' '),
+            l if l.starts_with('n') => {
+                Completion::new(0, vec![format!("{}n", l)]).with_retrigger(true)
+            }
+            _ => Completion::new(0, vec!["dir/".to_owned(), "doc".to_owned()])
+                .with_completion_type(CompletionType::List),
+        })
+    }
+}
+impl Hinter for DirCompleter {
+    type Hint = String;
+}
+impl Helper for DirCompleter {}
+impl Highlighter for DirCompleter {}
+impl Validator for DirCompleter {}
+
+#[test]
+fn complete_line_options() {
+    let config = Config::default();
+    let bindings = Bindings::new();
+    for (line, expected) in [
+        // re-triggered, then suffixed
+        ("d", "dir/file "),
+        // re-triggered at most 16 times, then cycled
+        ("n", "nnnnnnnnnnnnnnnnnn"),
+        // listed instead of cycled
+        ("x", "x"),
+    ] {
+        let mut out = Sink::default();
+        let history = crate::history::History::new();
+        let helper = Some(DirCompleter);
+        let mut s = init_state(&mut out, line, 1, helper.as_ref(), &history);
+        let mut input_state = InputState::new(&config, &bindings);
+        let mut rdr: IntoIter<KeyEvent> = vec![E::ENTER].into_iter();
+        super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+        assert_eq!(expected, s.line.as_str());
+    }
+}
+
+struct MenuCompleter;
+impl Completer for MenuCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<String>)> {
+        Ok((
+            0,
+            vec!["alpha".to_owned(), "beta".to_owned(), "gamma".to_owned()],
+        ))
+    }
+}
+impl Hinter for MenuCompleter {
+    type Hint = String;
+}
+impl Helper for MenuCompleter {}
+impl Highlighter for MenuCompleter {}
+impl Validator for MenuCompleter {}
+
+#[test]
+fn menu_complete() {
+    let config = Config::builder()
+        .completion_type(CompletionType::Menu)
+        .build();
+    let bindings = Bindings::new();
+    for (keys, line) in [
+        (
+            vec![
+                E(K::Tab, M::NONE),
+                E(K::Tab, M::NONE),
+                E(K::BackTab, M::NONE),
+                E::ENTER,
+            ],
+            "beta",
+        ),
+        (
+            vec![E(K::Right, M::NONE), E(K::Right, M::NONE), E::ENTER],
+            "gamma",
+        ),
+        (vec![E(K::Left, M::NONE), E::ENTER], "gamma"),
+        (vec![E(K::Tab, M::NONE), E::ESC], "a"),
+    ] {
+        let mut out = Sink::default();
+        let history = crate::history::History::new();
+        let helper = Some(MenuCompleter);
+        let mut s = init_state(&mut out, "a", 1, helper.as_ref(), &history);
+        let mut input_state = InputState::new(&config, &bindings);
+        let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+        let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+        assert_eq!(None, cmd);
+        assert_eq!(line, s.line.as_str());
+    }
+}
+
+struct SlowCompleter(std::time::Duration);
+impl Completer for SlowCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<String>)> {
+        std::thread::sleep(self.0);
+        Ok((0, vec![line.to_owned() + "t"]))
+    }
+}
+
+#[test]
+fn complete_in_background() {
+    use crate::completion::BackgroundCompleter;
+    use std::time::Duration;
+
+    let config = Config::default();
+    let bindings = Bindings::new();
+    for (delay, keys, cmd, line) in [
+        (Duration::ZERO, vec![], None, "rust"),
+        // cancelled by a key press, which is processed as usual
+        (
+            Duration::from_secs(1),
+            vec![E::from('x')],
+            Some(Cmd::SelfInsert(1, 'x')),
+            "rus",
+        ),
+        (Duration::from_secs(1), vec![E::ctrl('G')], None, "rus"),
+    ] {
+        let mut out = Sink::default();
+        let history = crate::history::History::new();
+        let completer = BackgroundCompleter::new(SlowCompleter(delay));
+        let mut s = init_state(&mut out, "rus", 3, None::<&()>, &history);
+        let mut input_state = InputState::new(&config, &bindings);
+        let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+        let pending = completer
+            .complete_in_background(&s.line, s.line.pos(), &s.ctx)
+            .unwrap();
+        let result = super::wait_completion(&mut rdr, &mut s, &mut input_state, &pending).unwrap();
+        match result {
+            Ok(Completion {
+                start, candidates, ..
+            }) => {
+                assert_eq!(None, cmd);
+                completer.update(&mut s.line, start, &candidates[0]);
+            }
+            Err(next) => assert_eq!(cmd, next),
+        }
+        assert_eq!(line, s.line.as_str());
+    }
+}
+
+#[test]
+fn auto_complete() {
+    let config = Config::builder()
+        .auto_complete(true)
+        .auto_complete_delay(0)
+        .build();
+    for (keys, line) in [
+        (vec![E::from('x'), E::ENTER], "ax"),
+        (
+            vec![
+                E::from('x'),
+                E(K::Tab, M::NONE),
+                E(K::Down, M::NONE),
+                E(K::Down, M::NONE),
+                E(K::Up, M::NONE),
+                E(K::Tab, M::NONE),
+                E::ENTER,
+            ],
+            "beta",
+        ),
+        // Up and Down browse the history until the candidates are focused
+        (vec![E::from('x'), E(K::Up, M::NONE), E::ENTER], "previous"),
+        // Escape hides the candidates
+        (
+            vec![
+                E::from('x'),
+                E(K::Tab, M::NONE),
+                E(K::Down, M::NONE),
+                E::ESC,
+                E::ENTER,
+            ],
+            "ax",
+        ),
+        // the candidates are updated after each key press
+        (
+            vec![
+                E::from('x'),
+                E::from('y'),
+                E(K::Tab, M::NONE),
+                E(K::Tab, M::NONE),
+                E::ENTER,
+            ],
+            "alpha",
+        ),
+    ] {
+        let mut editor = Editor::with_config(config.clone());
+        editor.add_history_entry("previous");
+        editor.set_helper(Some(MenuCompleter));
+        editor.term.keys.extend(keys);
+        assert_eq!(line, editor.readline_with_initial("", ("a", "")).unwrap());
+    }
+}
+
+#[test]
+fn page_completions() {
+    let candidates: Vec<String> = (0..100).map(|i| format!("{:0>30}", i)).collect();
+    let mut out = Sink::default();
+    let history = crate::history::History::new();
+    let helper = Some(SimpleCompleter);
+    let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+    let config = Config::default();
+    let bindings = Bindings::new();
+    let mut input_state = InputState::new(&config, &bindings);
+
+    let columns = crate::Columns::new(&candidates, 80);
+    let rows = super::candidate_rows(&s, &columns, &candidates, None);
+    assert_eq!(50, rows.len());
+    assert!(rows[0].starts_with(&candidates[0]));
+    assert!(rows[0].ends_with(&candidates[50]));
+    let rows = super::candidate_rows(&s, &columns, &candidates, Some(&[42]));
+    assert_eq!(vec![candidates[42].clone()], rows);
+
+    // next page, previous page, previous line, then filter
+    let keys = vec![
+        E::from(' '),
+        E::from('b'),
+        E(K::Up, M::NONE),
+        E::from('/'),
+        E::from('4'),
+        E::from('2'),
+        E::ENTER,
+    ];
+    let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+    let cmd =
+        super::page_completions(&mut rdr, &mut s, &mut input_state, &candidates, &config).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!(0, rdr.len());
+
+    // the filter follows `completion_ignore_case`
+    let candidates = vec!["README".to_owned(), "src".to_owned()];
+    for (ignore_case, filtered) in [(false, None), (true, Some(vec![0]))] {
+        let config = Config::builder()
+            .completion_ignore_case(ignore_case)
+            .build();
+        let mut rdr: IntoIter<KeyEvent> = vec![E::from('r'), E::from('e'), E::ENTER].into_iter();
+        let result =
+            super::filter_candidates(&mut rdr, &mut s, &mut input_state, &candidates, &config);
+        assert_eq!(filtered, result.unwrap());
+    }
+}
+
+#[test]
+fn columns() {
+    use crate::completion::{Candidate, DescribedPair};
+    use crate::Columns;
+
+    let candidates = vec![
+        DescribedPair {
+            display: "add".to_owned(),
+            replacement: "add".to_owned(),
+            description: Some("Add file contents to the index".to_owned()),
+        },
+        DescribedPair {
+            display: "commit".to_owned(),
+            replacement: "commit".to_owned(),
+            description: Some("Record changes".to_owned()),
+        },
+    ];
+    let render = |columns: &Columns, candidate: &DescribedPair| {
+        let mut ab = candidate.display().to_owned();
+        let width = columns.push_description(&mut ab, candidate, candidate.display().len());
+        columns.pad(&mut ab, width);
+        ab
+    };
+    let columns = Columns::new(&candidates, 80);
+    assert_eq!(2, columns.num_cols);
+    assert_eq!(
+        "add     Add file contents to the index  ",
+        render(&columns, &candidates[0])
+    );
+    assert_eq!(
+        "commit  Record changes                  ",
+        render(&columns, &candidates[1])
+    );
+    let columns = Columns::new(&candidates, 20);
+    assert_eq!("add     Add file …  ", render(&columns, &candidates[0]));
+    assert_eq!("commit  Record ch…  ", render(&columns, &candidates[1]));
+
+    let columns = Columns::new(&["a", "bc"], 80);
+    assert_eq!(20, columns.num_cols);
+}
+
 // `keys`: keys to press
 // `expected_line`: line after enter key
 fn assert_line(mode: EditMode, keys: &[KeyEvent], expected_line: &str) {
@@ -103,6 +479,25 @@ fn assert_cursor(mode: EditMode, initial: (&str, &str), keys: &[KeyEvent], expec
     assert_eq!(expected.0.len(), editor.term.cursor);
 }
 
+// `binding`: custom key binding
+// `initial`: line status before `keys` pressed: strings before and after cursor
+// `keys`: keys to press
+// `expected`: line status before enter key: strings before and after cursor
+#[cfg(feature = "custom-bindings")]
+fn assert_cursor_with_binding(
+    mode: EditMode,
+    binding: (KeyEvent, Cmd),
+    initial: (&str, &str),
+    keys: &[KeyEvent],
+    expected: (&str, &str),
+) {
+    let mut editor = init_editor(mode, keys);
+    editor.bind_sequence(binding.0, binding.1);
+    let actual_line = editor.readline_with_initial("", initial).unwrap();
+    assert_eq!(expected.0.to_owned() + expected.1, actual_line);
+    assert_eq!(expected.0.len(), editor.term.cursor);
+}
+
 // `entries`: history entries before `keys` pressed
 // `keys`: keys to press
 // `expected`: line status before enter key: strings before and after cursor
@@ -1,7 +1,11 @@
 //! Vi command mode specific key bindings
+#[cfg(feature = "custom-bindings")]
+use super::assert_cursor_with_binding;
 use super::{assert_cursor, assert_history};
 use crate::config::EditMode;
 use crate::keys::KeyEvent as E;
+#[cfg(feature = "custom-bindings")]
+use crate::Cmd;
 
 #[test]
 fn dollar() {
@@ -447,6 +451,50 @@ fn r() {
     );
 }
 
+#[test]
+fn uppercase_u() {
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello", ", world"),
+        &[E::ESC, E::from('x'), E::from('x'), E::from('U'), E::ENTER],
+        ("Hello", ", world"),
+    );
+    // revert-line is undoable
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello", ", world"),
+        &[E::ESC, E::from('D'), E::from('U'), E::from('u'), E::ENTER],
+        ("Hell", ""),
+    );
+}
+
+#[test]
+#[cfg(feature = "custom-bindings")]
+fn ctrl_r() {
+    let binding = (E::ctrl('R'), Cmd::Redo(1));
+    assert_cursor_with_binding(
+        EditMode::Vi,
+        binding.clone(),
+        ("Hello", ", world"),
+        &[E::ESC, E::from('x'), E::from('u'), E::ctrl('R'), E::ENTER],
+        ("Hell", ", world"),
+    );
+    assert_cursor_with_binding(
+        EditMode::Vi,
+        binding,
+        ("", "Hello world"),
+        &[
+            E::ESC,
+            E::from('d'),
+            E::from('w'),
+            E::from('u'),
+            E::ctrl('R'),
+            E::ENTER,
+        ],
+        ("", "world"),
+    );
+}
+
 #[test]
 fn s() {
     assert_cursor(
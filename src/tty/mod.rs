@@ -30,6 +30,9 @@ pub trait RawReader {
     /// For CTRL-V support
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char>;
+    /// Wait at most `timeout_ms` for a key press: return `true` when a key
+    /// can be read without blocking.
+    fn poll_input(&mut self, timeout_ms: i32) -> Result<bool>;
     /// Bracketed paste
     fn read_pasted_text(&mut self) -> Result<String>;
     /// Check if `key` is bound to a peculiar command
@@ -38,6 +38,10 @@ impl<'a> RawReader for Iter<'a, KeyEvent> {
         unimplemented!();
     }
 
+    fn poll_input(&mut self, _: i32) -> Result<bool> {
+        Ok(!self.as_slice().is_empty())
+    }
+
     fn read_pasted_text(&mut self) -> Result<String> {
         unimplemented!()
     }
@@ -69,6 +73,10 @@ impl RawReader for IntoIter<KeyEvent> {
         }
     }
 
+    fn poll_input(&mut self, _: i32) -> Result<bool> {
+        Ok(!self.as_slice().is_empty())
+    }
+
     fn read_pasted_text(&mut self) -> Result<String> {
         unimplemented!()
     }
@@ -776,6 +776,10 @@ impl RawReader for PosixRawReader {
         }
     }
 
+    fn poll_input(&mut self, timeout_ms: i32) -> Result<bool> {
+        Ok(self.poll(timeout_ms)? > 0)
+    }
+
     fn read_pasted_text(&mut self) -> Result<String> {
         let mut buffer = String::new();
         loop {
@@ -168,6 +168,19 @@ impl RawReader for ConsoleRawReader {
         read_input(self.conin, u32::MAX, self.enable_bracketed_paste)
     }
 
+    fn poll_input(&mut self, timeout_ms: i32) -> Result<bool> {
+        use std::convert::TryInto;
+        use winapi::um::synchapi::WaitForSingleObject;
+        use winapi::um::winbase::{INFINITE, WAIT_OBJECT_0};
+
+        let timeout = timeout_ms.try_into().unwrap_or(INFINITE);
+        match unsafe { WaitForSingleObject(self.conin, timeout) } {
+            WAIT_OBJECT_0 => Ok(true),
+            winerror::WAIT_TIMEOUT => Ok(false),
+            _ => Err(io::Error::last_os_error())?,
+        }
+    }
+
     fn read_pasted_text(&mut self) -> Result<String> {
         if self.enable_bracketed_paste {
             escape::read_pasted_text(self)
@@ -50,7 +50,6 @@ impl Change {
         }
     }
 
-    #[cfg(test)]
     fn redo(&self, line: &mut LineBuffer) {
         match *self {
             Change::Begin | Change::End => {
@@ -58,6 +57,7 @@ impl Change {
             }
             Change::Insert { idx, ref text } => {
                 line.insert_str(idx, text);
+                line.set_pos(idx + text.len());
             }
             Change::Delete { idx, ref text } => {
                 line.delete_range(idx..idx + text.len());
@@ -115,7 +115,6 @@ impl Changeset {
 
     pub fn begin(&mut self) -> usize {
         debug!(target: "rustyline", "Changeset::begin");
-        self.redos.clear();
         let mark = self.undos.len();
         self.undos.push(Change::Begin);
         self.undo_group_level += 1;
@@ -126,7 +125,6 @@ impl Changeset {
     /// this `end`.
     pub fn end(&mut self) -> bool {
         debug!(target: "rustyline", "Changeset::end");
-        self.redos.clear();
         let mut touched = false;
         while self.undo_group_level > 0 {
             self.undo_group_level -= 1;
@@ -288,8 +286,8 @@ impl Changeset {
         self.undos.truncate(len);
     }
 
-    #[cfg(test)]
     pub fn redo(&mut self, line: &mut LineBuffer) -> bool {
+        debug!(target: "rustyline", "Changeset::redo");
         let mut waiting_for_end = 0;
         let mut redone = false;
         loop {
@@ -465,6 +463,30 @@ mod tests {
         assert_eq!(buf.as_str(), "Hi, world!");
     }
 
+    #[test]
+    fn test_redo_invalidation() {
+        let mut buf = LineBuffer::init("", 0, None);
+        buf.insert_str(0, "Hello");
+        let mut cs = Changeset::new();
+        cs.insert_str(0, "Hello");
+
+        cs.undo(&mut buf, 1);
+        assert_eq!(buf.as_str(), "");
+        // empty group: nothing changed
+        cs.begin();
+        cs.end();
+        assert_eq!(1, cs.redos.len());
+
+        cs.redo(&mut buf);
+        assert_eq!(buf.as_str(), "Hello");
+        assert_eq!(buf.pos(), 5);
+
+        cs.undo(&mut buf, 1);
+        cs.insert(0, 'a');
+        assert_eq!(0, cs.redos.len());
+        assert!(!cs.redo(&mut buf));
+    }
+
     #[test]
     fn test_last_insert() {
         let mut cs = Changeset::new();
@@ -0,0 +1,66 @@
+//! `#[derive(Completer)]` must forward every method to the annotated field.
+use rustyline::completion::{Completer, Completion, PendingCompletion};
+use rustyline::history::History;
+use rustyline::{Context, Result};
+use rustyline_derive::Completer;
+
+struct SlowCompleter;
+
+impl Completer for SlowCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<String>)> {
+        Ok((0, vec!["ls".to_owned()]))
+    }
+
+    fn completion(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<Completion<String>> {
+        Ok(Completion::new(0, vec!["ls".to_owned()]).with_suffix(' '))
+    }
+
+    fn complete_in_background(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Option<PendingCompletion<String>> {
+        let (pending, _sender) = PendingCompletion::new();
+        Some(pending)
+    }
+}
+
+#[derive(Completer)]
+struct MyHelper {
+    #[rustyline(Completer)]
+    completer: SlowCompleter,
+}
+
+#[test]
+fn complete_in_background() {
+    let history = History::new();
+    let ctx = Context::new(&history);
+    let helper = MyHelper {
+        completer: SlowCompleter,
+    };
+    assert!(helper.complete_in_background("l", 1, &ctx).is_some());
+}
+
+#[test]
+fn completion() {
+    let history = History::new();
+    let ctx = Context::new(&history);
+    let helper = MyHelper {
+        completer: SlowCompleter,
+    };
+    let completion = helper.completion("l", 1, &ctx).unwrap();
+    assert_eq!(Some(' '), completion.suffix());
+}